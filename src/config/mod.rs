@@ -1,6 +1,8 @@
 use crate::repo::CheckOutInfo;
 use log::debug;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::PathBuf;
 use tokio::fs;
 
@@ -10,19 +12,179 @@ pub struct Repository {
     pub organization: String,
     pub cloned_locally: bool,
     pub checkout_info: CheckOutInfo,
+    /// Extra environment variables exported to commands run in this repository
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Hooks for this repository, run after the workspace wide hooks
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// How the repository is cloned when it is missing
+    #[serde(default)]
+    pub clone_options: CloneOptions,
+    /// Directories checked out, everything when empty
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
+    /// Initialise and update submodules, recursively, after cloning and pulling
+    #[serde(default)]
+    pub recurse_submodules: bool,
+    /// Remotes besides origin by name and URL, added to the clone when missing
+    #[serde(default)]
+    pub remotes: BTreeMap<String, String>,
+    /// Remote pulled from, origin when unset
+    #[serde(default)]
+    pub pull_remote: Option<String>,
+    /// Remote pushed to, origin when unset
+    #[serde(default)]
+    pub push_remote: Option<String>,
 }
+
+impl Repository {
+    /// Name of the remote changes are pulled from
+    pub fn pull_from(&self) -> &str {
+        self.pull_remote.as_deref().unwrap_or("origin")
+    }
+    /// Name of the remote changes are pushed to
+    pub fn push_to(&self) -> &str {
+        self.push_remote.as_deref().unwrap_or("origin")
+    }
+}
+/// Options for cloning less than the full repository
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// Only fetch this many commits of history
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// Only fetch the branch that is checked out
+    #[serde(default)]
+    pub single_branch: bool,
+    /// Branch to check out instead of the remote's default branch
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Partial clone filter such as `blob:none`, cloned with the git command line
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+/// Points around palette operations where hooks run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PrePush,
+    PostPull,
+    PostClone,
+    PostSwitch,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Hook::PrePush => "pre_push",
+            Hook::PostPull => "post_pull",
+            Hook::PostClone => "post_clone",
+            Hook::PostSwitch => "post_switch",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Shell command lines to run in the repository directory for each hook
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Hooks {
+    #[serde(default)]
+    pub pre_push: Vec<String>,
+    #[serde(default)]
+    pub post_pull: Vec<String>,
+    #[serde(default)]
+    pub post_clone: Vec<String>,
+    #[serde(default)]
+    pub post_switch: Vec<String>,
+}
+
+impl Hooks {
+    pub fn commands(&self, hook: Hook) -> &[String] {
+        match hook {
+            Hook::PrePush => &self.pre_push,
+            Hook::PostPull => &self.post_pull,
+            Hook::PostClone => &self.post_clone,
+            Hook::PostSwitch => &self.post_switch,
+        }
+    }
+}
+/// Shell used to interpret command lines, invoked as `<program> <args...> <command>`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ShellConfig {
+    pub program: String,
+    #[serde(default = "ShellConfig::default_args")]
+    pub args: Vec<String>,
+}
+
+impl ShellConfig {
+    fn default_args() -> Vec<String> {
+        vec!["-c".to_string()]
+    }
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        ShellConfig {
+            program: "/bin/sh".to_string(),
+            args: ShellConfig::default_args(),
+        }
+    }
+}
+
+/// What happens to the rest of a user defined command when a step fails
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    #[default]
+    Stop,
+    Continue,
+}
+
+/// One step of a user defined command, either a palette subcommand or a shell command line
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CommandStep {
+    /// Palette subcommand and its arguments, for example `["pull"]`
+    #[serde(default)]
+    pub palette: Vec<String>,
+    /// Command line run in every selected repository, as `palette exec` would
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Repositories the step is limited to, all selected repositories when empty
+    #[serde(default)]
+    pub repos: Vec<String>,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+/// A named alias or multi-step workflow runnable with `palette run <name>`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UserCommand {
+    #[serde(default)]
+    pub description: String,
+    pub steps: Vec<CommandStep>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Configuration {
     pub configuration_path: String,
     pub configuration_file_name: String,
     pub configuration_full_path: String,
     pub repository: Vec<Repository>,
+    #[serde(default)]
+    pub shell: ShellConfig,
+    #[serde(default)]
+    pub commands: BTreeMap<String, UserCommand>,
+    /// Hooks run for every repository
+    #[serde(default)]
+    pub hooks: Hooks,
 }
-pub(crate) trait Saves {
+#[allow(async_fn_in_trait)]
+pub trait Saves {
     async fn save(&self) -> Result<(), anyhow::Error>;
 }
 
-pub(crate) trait Loads {
+#[allow(async_fn_in_trait)]
+pub trait Loads {
     async fn load(&mut self) -> Result<(), anyhow::Error>;
 }
 
@@ -63,6 +225,9 @@ impl Default for Configuration {
             configuration_file_name: default_name,
             configuration_full_path: path.as_path().to_str().unwrap().to_string(),
             repository: vec![],
+            shell: ShellConfig::default(),
+            commands: BTreeMap::new(),
+            hooks: Hooks::default(),
         }
     }
 }
@@ -77,6 +242,31 @@ impl Configuration {
     pub fn get_repository(&self) -> &Vec<Repository> {
         &self.repository
     }
+    /// Commands for a hook, the workspace wide ones followed by the repository's own
+    pub fn hook_commands(&self, repo: &Repository, hook: Hook) -> Vec<String> {
+        self.hooks
+            .commands(hook)
+            .iter()
+            .chain(repo.hooks.commands(hook))
+            .cloned()
+            .collect()
+    }
+    /// An empty configuration for another workspace, sharing this one's shell, commands and hooks
+    pub fn workspace(&self) -> Configuration {
+        Configuration {
+            shell: self.shell.clone(),
+            commands: self.commands.clone(),
+            hooks: self.hooks.clone(),
+            ..Configuration::default()
+        }
+    }
+    /// Repositories matching the given names, or every repository when no names are given
+    pub fn select_repositories(&self, names: &[String]) -> Vec<&Repository> {
+        self.repository
+            .iter()
+            .filter(|r| names.is_empty() || names.contains(&r.name))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -97,6 +287,7 @@ mod tests {
             configuration_file_name: config_file.to_string(),
             configuration_full_path: path.to_str().unwrap().to_string(),
             repository: vec![],
+            ..Default::default()
         }
     }
 
@@ -109,6 +300,7 @@ mod tests {
                 branch_name: "main".to_string(),
                 commit_sha: "abc123def456".to_string(),
             },
+            ..Default::default()
         }
     }
 
@@ -247,6 +439,7 @@ mod tests {
             organization: "org1".to_string(),
             cloned_locally: true,
             checkout_info: CheckOutInfo::default(),
+            ..Default::default()
         };
 
         let repo2 = Repository {
@@ -254,6 +447,7 @@ mod tests {
             organization: "org2".to_string(),
             cloned_locally: false,
             checkout_info: CheckOutInfo::default(),
+            ..Default::default()
         };
 
         config.add_repository(repo1);
@@ -266,4 +460,191 @@ mod tests {
         let found_repo = config.get_repository_mut("repo1".to_string());
         assert_eq!(found_repo.organization, "org1");
     }
+
+    #[tokio::test]
+    async fn test_repository_env_defaults_when_missing() {
+        let json = r#"{
+            "name": "test-repo",
+            "organization": "test-org",
+            "cloned_locally": true,
+            "checkout_info": { "branch_name": "main", "commit_sha": "abc123" }
+        }"#;
+        let repo: Repository = serde_json::from_str(json).expect("Failed to deserialize");
+        assert!(repo.env.is_empty());
+
+        let json = r#"{
+            "name": "test-repo",
+            "organization": "test-org",
+            "cloned_locally": true,
+            "checkout_info": { "branch_name": "main", "commit_sha": "abc123" },
+            "env": { "RUST_LOG": "debug" }
+        }"#;
+        let repo: Repository = serde_json::from_str(json).expect("Failed to deserialize");
+        assert_eq!(repo.env.get("RUST_LOG").map(String::as_str), Some("debug"));
+    }
+
+    #[tokio::test]
+    async fn test_repository_recurse_submodules() {
+        let repo = create_test_repository();
+        assert!(!repo.recurse_submodules);
+
+        let json = r#"{
+            "name": "test-repo",
+            "organization": "test-org",
+            "cloned_locally": true,
+            "checkout_info": { "branch_name": "main", "commit_sha": "abc123" },
+            "recurse_submodules": true
+        }"#;
+        let repo: Repository = serde_json::from_str(json).expect("Failed to deserialize");
+        assert!(repo.recurse_submodules);
+    }
+
+    #[tokio::test]
+    async fn test_repository_clone_options() {
+        let json = r#"{
+            "name": "test-repo",
+            "organization": "test-org",
+            "cloned_locally": false,
+            "checkout_info": { "branch_name": "", "commit_sha": "" },
+            "clone_options": { "depth": 1, "single_branch": true, "filter": "blob:none" }
+        }"#;
+        let repo: Repository = serde_json::from_str(json).expect("Failed to deserialize");
+        assert_eq!(
+            repo.clone_options,
+            CloneOptions {
+                depth: Some(1),
+                single_branch: true,
+                branch: None,
+                filter: Some("blob:none".to_string()),
+            }
+        );
+        assert_eq!(Repository::default().clone_options, CloneOptions::default());
+    }
+
+    #[tokio::test]
+    async fn test_shell_configuration() {
+        let config = Configuration::default();
+        assert_eq!(config.shell.program, "/bin/sh");
+        assert_eq!(config.shell.args, vec!["-c".to_string()]);
+
+        let json = r#"{
+            "configuration_path": ".",
+            "configuration_file_name": "config.palette",
+            "configuration_full_path": "./config.palette",
+            "repository": [],
+            "shell": { "program": "bash" }
+        }"#;
+        let config: Configuration = serde_json::from_str(json).expect("Failed to deserialize");
+        assert_eq!(config.shell.program, "bash");
+        assert_eq!(config.shell.args, vec!["-c".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_user_commands_configuration() {
+        let json = r#"{
+            "configuration_path": ".",
+            "configuration_file_name": "config.palette",
+            "configuration_full_path": "./config.palette",
+            "repository": [],
+            "commands": {
+                "build": {
+                    "description": "Update and build everything",
+                    "steps": [
+                        { "palette": ["pull"] },
+                        { "shell": "cargo build", "repos": ["api"], "on_failure": "continue" }
+                    ]
+                }
+            }
+        }"#;
+        let config: Configuration = serde_json::from_str(json).expect("Failed to deserialize");
+        let build = &config.commands["build"];
+        assert_eq!(build.steps.len(), 2);
+        assert_eq!(build.steps[0].palette, vec!["pull".to_string()]);
+        assert_eq!(build.steps[0].on_failure, FailurePolicy::Stop);
+        assert_eq!(build.steps[1].shell.as_deref(), Some("cargo build"));
+        assert_eq!(build.steps[1].repos, vec!["api".to_string()]);
+        assert_eq!(build.steps[1].on_failure, FailurePolicy::Continue);
+    }
+
+    #[tokio::test]
+    async fn test_hook_commands_combine_workspace_and_repository() {
+        let mut config = Configuration::default();
+        config.hooks.pre_push.push("cargo test".to_string());
+        let mut repo = create_test_repository();
+        repo.hooks.pre_push.push("cargo clippy".to_string());
+        repo.hooks.post_pull.push("npm install".to_string());
+
+        assert_eq!(
+            config.hook_commands(&repo, Hook::PrePush),
+            vec!["cargo test".to_string(), "cargo clippy".to_string()]
+        );
+        assert_eq!(
+            config.hook_commands(&repo, Hook::PostPull),
+            vec!["npm install".to_string()]
+        );
+        assert!(config.hook_commands(&repo, Hook::PostSwitch).is_empty());
+        assert_eq!(Hook::PostClone.to_string(), "post_clone");
+    }
+
+    #[tokio::test]
+    async fn test_select_repositories() {
+        let mut config = Configuration::default();
+        for name in ["repo1", "repo2", "repo3"] {
+            config.add_repository(Repository {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+
+        assert_eq!(config.select_repositories(&[]).len(), 3);
+
+        let selected = config.select_repositories(&["repo1".to_string(), "repo3".to_string()]);
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[0].name, "repo1");
+        assert_eq!(selected[1].name, "repo3");
+
+        assert!(
+            config
+                .select_repositories(&["missing".to_string()])
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_workspace_keeps_settings_but_not_repositories() {
+        let mut config = Configuration::default();
+        config.add_repository(Repository::default());
+        config.shell.program = "zsh".to_string();
+        config.hooks.post_pull = vec!["npm install".to_string()];
+
+        let workspace = config.workspace();
+        assert!(workspace.get_repository().is_empty());
+        assert_eq!(workspace.shell.program, "zsh");
+        assert_eq!(workspace.hooks.post_pull, vec!["npm install".to_string()]);
+        assert_eq!(workspace.configuration_full_path, "./config.palette");
+    }
+
+    #[tokio::test]
+    async fn test_repository_remotes() {
+        let repo = create_test_repository();
+        assert!(repo.remotes.is_empty());
+        assert_eq!(repo.pull_from(), "origin");
+        assert_eq!(repo.push_to(), "origin");
+
+        let json = r#"{
+            "name": "test-repo",
+            "organization": "test-org",
+            "cloned_locally": true,
+            "checkout_info": { "branch_name": "main", "commit_sha": "abc123" },
+            "remotes": { "upstream": "git@github.com:upstream-org/test-repo.git" },
+            "pull_remote": "upstream"
+        }"#;
+        let repo: Repository = serde_json::from_str(json).expect("Failed to deserialize");
+        assert_eq!(
+            repo.remotes.get("upstream").map(String::as_str),
+            Some("git@github.com:upstream-org/test-repo.git")
+        );
+        assert_eq!(repo.pull_from(), "upstream");
+        assert_eq!(repo.push_to(), "origin");
+    }
 }
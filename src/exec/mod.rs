@@ -0,0 +1,700 @@
+use crate::config::{Hook, Repository, ShellConfig};
+use crate::repo::{Branches, Manager, Statuses};
+use anyhow::anyhow;
+use clap::ValueEnum;
+use colorize::AnsiColor;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// How the output of each repository is laid out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputStyle {
+    /// A header per repository followed by the command's untouched output
+    #[default]
+    Sectioned,
+    /// Every line is prefixed with the repository name
+    Prefixed,
+}
+
+/// What to run in each repository
+#[derive(Clone, Debug)]
+pub enum ExecCommand {
+    /// A command line interpreted by the configured shell
+    Shell(String),
+    /// A program and its arguments, run directly without a shell
+    Argv(Vec<String>),
+}
+
+impl ExecCommand {
+    /// Build the process to spawn, with placeholders already expanded
+    fn to_command(&self, shell: &ShellConfig, variables: &[(&str, String)]) -> Command {
+        match self {
+            ExecCommand::Shell(line) => {
+                // Values are quoted so the shell takes them literally, spaces and all
+                let quoted: Vec<(&str, String)> = variables
+                    .iter()
+                    .map(|(key, value)| (*key, shell_quote(value)))
+                    .collect();
+                let mut command = Command::new(&shell.program);
+                command
+                    .args(&shell.args)
+                    .arg(expand_placeholders(line, &quoted));
+                command
+            }
+            ExecCommand::Argv(argv) => {
+                let mut argv = argv.iter().map(|arg| expand_placeholders(arg, variables));
+                let mut command = Command::new(argv.next().unwrap_or_default());
+                command.args(argv);
+                command
+            }
+        }
+    }
+}
+
+/// Prepare a process running in the repository directory with its variables exported
+fn repository_command(repo: &Repository, command: &ExecCommand, shell: &ShellConfig) -> Command {
+    let variables = repository_variables(repo);
+    let mut child = command.to_command(shell, &variables);
+    child
+        .envs(
+            variables
+                .iter()
+                .map(|(key, value)| (format!("PALETTE_REPO_{}", key.to_uppercase()), value)),
+        )
+        .envs(&repo.env)
+        // use repo name as our path
+        .current_dir(&repo.name);
+    child
+}
+
+/// Settings shared by every repository in a single exec run
+#[derive(Clone, Debug, Default)]
+pub struct ExecOptions {
+    pub style: OutputStyle,
+    pub shell: ShellConfig,
+    pub timeout: Option<Duration>,
+    /// Directory receiving one `<repo>.log` file per repository
+    pub log_dir: Option<PathBuf>,
+    /// Width the repository name is padded to in prefixed output
+    pub prefix_width: usize,
+}
+
+/// Predicates a repository has to satisfy before a command is run in it
+#[derive(Clone, Debug, Default)]
+pub struct RunConditions {
+    /// Paths, relative to the repository root, that must all exist
+    pub if_exists: Vec<String>,
+    /// Glob patterns, relative to the repository root, that must each match at least one file
+    pub if_file_matches: Vec<String>,
+    pub if_dirty: bool,
+    pub if_ahead: bool,
+}
+
+impl RunConditions {
+    /// Whether the command should run in this repository
+    pub fn matches(&self, repo: &Repository) -> Result<bool, anyhow::Error> {
+        let root = Path::new(&repo.name);
+        if !self.if_exists.iter().all(|path| root.join(path).exists()) {
+            return Ok(false);
+        }
+        for pattern in &self.if_file_matches {
+            let full_pattern = root.join(pattern);
+            let mut matches = glob::glob(&full_pattern.to_string_lossy())?;
+            if !matches.any(|entry| entry.is_ok()) {
+                return Ok(false);
+            }
+        }
+        if self.if_dirty || self.if_ahead {
+            let status = Manager::default().status(repo)?;
+            if (self.if_dirty && !status.dirty) || (self.if_ahead && status.ahead == 0) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Outcome of running a command in a single repository
+#[derive(Clone, Debug)]
+pub struct ExecResult {
+    pub repository: String,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+}
+
+impl ExecResult {
+    pub fn success(&self) -> bool {
+        self.error.is_none() && self.exit_code == Some(0)
+    }
+}
+
+/// Values describing a repository, available to commands as `{key}` placeholders
+/// and `PALETTE_REPO_<KEY>` environment variables
+pub fn repository_variables(repo: &Repository) -> Vec<(&'static str, String)> {
+    // Prefer what is checked out right now over what the configuration last recorded
+    let checkout = Manager::default()
+        .current_checkout(repo)
+        .unwrap_or_else(|_| repo.checkout_info.clone());
+    let path = std::fs::canonicalize(&repo.name)
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| repo.name.clone());
+    vec![
+        ("name", repo.name.clone()),
+        ("org", repo.organization.clone()),
+        ("branch", checkout.branch_name),
+        ("sha", checkout.commit_sha),
+        ("path", path),
+    ]
+}
+
+/// Replace `{key}` placeholders in a command with the repository's values
+pub fn expand_placeholders(command: &str, variables: &[(&str, String)]) -> String {
+    variables
+        .iter()
+        .fold(command.to_string(), |command, (key, value)| {
+            command.replace(&format!("{{{key}}}"), value)
+        })
+}
+
+/// Single quote a value for a POSIX shell, closing and reopening the quotes around any `'`
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Where the log for this run should go, `<workspace>/.palette/logs/<timestamp>-<pid>`,
+/// so runs started at the same moment do not share a directory
+pub fn log_directory(workspace: &str) -> PathBuf {
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+    Path::new(workspace)
+        .join(".palette")
+        .join("logs")
+        .join(format!("{timestamp}-{}", std::process::id()))
+}
+
+/// Copy lines from a child stream to our own output and the log as they arrive
+async fn forward_lines<R: AsyncRead + Unpin>(
+    reader: R,
+    prefix: Option<String>,
+    to_stderr: bool,
+    log: Option<Arc<Mutex<File>>>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let line = match &prefix {
+            Some(prefix) => format!("{prefix} | {line}"),
+            None => line,
+        };
+        // A failed write to the terminal or log should not abort the command
+        if to_stderr {
+            let _ = writeln!(io::stderr().lock(), "{line}");
+        } else {
+            let _ = writeln!(io::stdout().lock(), "{line}");
+        }
+        if let Some(log) = &log {
+            let _ = writeln!(log.lock().unwrap(), "{line}");
+        }
+    }
+}
+
+/// Process groups of commands run with a timeout, which Ctrl-C at the terminal no longer reaches
+static DETACHED_GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Signal the shell and anything it started, they all share its process group
+fn signal_process_group(group: u32, signal: libc::c_int) {
+    unsafe {
+        libc::kill(-(group as i32), signal);
+    }
+}
+
+fn forget_process_group(group: Option<u32>) {
+    if let Some(group) = group {
+        DETACHED_GROUPS.lock().unwrap().retain(|g| *g != group);
+    }
+}
+
+/// Run a command in the repository directory, streaming stdout and stderr as they are produced
+pub async fn run_in_repository(
+    repo: &Repository,
+    command: &ExecCommand,
+    options: &ExecOptions,
+) -> ExecResult {
+    let failed = |error: String| ExecResult {
+        repository: repo.name.clone(),
+        exit_code: None,
+        error: Some(error),
+    };
+
+    let log_name = Path::new(&repo.name)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| repo.name.clone());
+    let log = match &options.log_dir {
+        Some(dir) => match File::create(dir.join(format!("{log_name}.log"))) {
+            Ok(file) => Some(Arc::new(Mutex::new(file))),
+            Err(e) => return failed(format!("unable to create log file: {e}")),
+        },
+        None => None,
+    };
+
+    let prefix = match options.style {
+        OutputStyle::Sectioned => {
+            println!("{}", format!("==> {}", repo.name).bold());
+            None
+        }
+        OutputStyle::Prefixed => Some(format!(
+            "{:width$}",
+            repo.name,
+            width = options.prefix_width
+        )),
+    };
+
+    let mut process = repository_command(repo, command, &options.shell);
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // A timeout kills the command together with whatever it started, which needs a
+    // process group of their own, otherwise they stay in the terminal's for Ctrl-C
+    if options.timeout.is_some() {
+        process.process_group(0);
+    }
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => return failed(e.to_string()),
+    };
+    let group = options.timeout.and(child.id());
+    if let Some(group) = group {
+        DETACHED_GROUPS.lock().unwrap().push(group);
+    }
+
+    let mut readers = JoinSet::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.spawn(forward_lines(stdout, prefix.clone(), false, log.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.spawn(forward_lines(stderr, prefix, true, log));
+    }
+
+    let status = match options.timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => status,
+            Err(_) => {
+                if let Some(group) = group {
+                    signal_process_group(group, libc::SIGKILL);
+                }
+                let _ = child.wait().await;
+                forget_process_group(group);
+                readers.abort_all();
+                return failed(format!("timed out after {}s", timeout.as_secs()));
+            }
+        },
+        None => child.wait().await,
+    };
+    forget_process_group(group);
+    readers.join_all().await;
+
+    match status {
+        Ok(status) => ExecResult {
+            repository: repo.name.clone(),
+            exit_code: status.code(),
+            error: None,
+        },
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+/// Run a hook's commands in the repository directory, stopping at the first one that fails
+pub async fn run_hook(
+    repo: &Repository,
+    hook: Hook,
+    commands: &[String],
+    shell: &ShellConfig,
+) -> Result<(), anyhow::Error> {
+    for line in commands {
+        println!("{}: running {} hook `{}`", repo.name, hook, line);
+        let status = repository_command(repo, &ExecCommand::Shell(line.clone()), shell)
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(anyhow!("{hook} hook `{line}` failed ({status})"));
+        }
+    }
+    Ok(())
+}
+
+/// Run the command across repositories, at most `parallel` at a time, returning results in repository order
+pub async fn run_all(
+    repos: Vec<Repository>,
+    command: ExecCommand,
+    options: ExecOptions,
+    parallel: usize,
+    fail_fast: bool,
+) -> Vec<ExecResult> {
+    let semaphore = Arc::new(Semaphore::new(parallel.max(1)));
+    let command = Arc::new(command);
+    let options = Arc::new(options);
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // Pass Ctrl-C on to commands in their own process group before giving up
+    let interrupt = options.timeout.is_some().then(|| {
+        tokio::spawn(async {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                for group in DETACHED_GROUPS.lock().unwrap().drain(..) {
+                    signal_process_group(group, libc::SIGINT);
+                }
+                std::process::exit(130);
+            }
+        })
+    });
+
+    let mut tasks = JoinSet::new();
+    for (position, repo) in repos.into_iter().enumerate() {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let (command, options, stop) = (command.clone(), options.clone(), stop.clone());
+        tasks.spawn(async move {
+            let result = run_in_repository(&repo, &command, &options).await;
+            if fail_fast && !result.success() {
+                stop.store(true, Ordering::SeqCst);
+            }
+            drop(permit);
+            (position, result)
+        });
+    }
+
+    let mut results = tasks.join_all().await;
+    if let Some(interrupt) = interrupt {
+        interrupt.abort();
+    }
+    results.sort_by_key(|(position, _)| *position);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Print a pass/fail line per repository
+pub fn print_summary(results: &[ExecResult]) {
+    let width = results
+        .iter()
+        .map(|r| r.repository.len())
+        .max()
+        .unwrap_or(0);
+    println!();
+    println!("{}", "Summary".bold());
+    for result in results {
+        let outcome = match (&result.error, result.exit_code) {
+            (Some(error), _) => format!("failed ({error})").red(),
+            (None, Some(0)) => "ok".green(),
+            (None, Some(code)) => format!("failed (exit code {code})").red(),
+            (None, None) => "failed (terminated by signal)".red(),
+        };
+        println!("  {:width$}  {}", result.repository, outcome);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn test_repository(path: &str) -> Repository {
+        Repository {
+            name: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn shell(line: &str) -> ExecCommand {
+        ExecCommand::Shell(line.to_string())
+    }
+
+    fn prefixed() -> ExecOptions {
+        ExecOptions {
+            style: OutputStyle::Prefixed,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_in_repository_reports_exit_code() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+
+        let ok = run_in_repository(&repo, &shell("true"), &prefixed()).await;
+        assert!(ok.success());
+
+        let failed = run_in_repository(&repo, &shell("echo oops >&2; exit 3"), &prefixed()).await;
+        assert!(!failed.success());
+        assert_eq!(failed.exit_code, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_run_in_missing_repository_fails() {
+        let repo = test_repository("/nonexistent/path");
+
+        let result = run_in_repository(&repo, &shell("true"), &ExecOptions::default()).await;
+        assert!(!result.success());
+        assert!(result.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_run_in_repository_times_out() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+        let options = ExecOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..prefixed()
+        };
+
+        let started = std::time::Instant::now();
+        let result = run_in_repository(&repo, &shell("sleep 30"), &options).await;
+        assert!(!result.success());
+        assert!(result.error.unwrap().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_run_in_repository_writes_log() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let log_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+        let options = ExecOptions {
+            log_dir: Some(log_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let result = run_in_repository(&repo, &shell("echo hello"), &options).await;
+        assert!(result.success());
+
+        let log_name = format!("{}.log", repo.name);
+        let log_file = log_dir
+            .path()
+            .join(Path::new(&log_name).file_name().unwrap());
+        let log = std::fs::read_to_string(log_file).expect("Failed to read log");
+        assert_eq!(log, "hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_only_commands_with_a_timeout_get_their_own_process_group() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+        let own_group = unsafe { libc::getpgrp() }.to_string();
+        let group_of = |timeout: Option<Duration>| {
+            let log_dir = tempdir().expect("Failed to create temp dir");
+            let options = ExecOptions {
+                log_dir: Some(log_dir.path().to_path_buf()),
+                timeout,
+                ..Default::default()
+            };
+            let repo = repo.clone();
+            async move {
+                let result = run_in_repository(&repo, &shell("ps -o pgid= -p $$"), &options).await;
+                assert!(result.success());
+                let log_name = Path::new(&repo.name).file_name().unwrap().to_os_string();
+                let log = log_dir
+                    .path()
+                    .join(format!("{}.log", log_name.to_string_lossy()));
+                std::fs::read_to_string(log).unwrap().trim().to_string()
+            }
+        };
+
+        assert_eq!(group_of(None).await, own_group);
+        assert_ne!(group_of(Some(Duration::from_secs(30))).await, own_group);
+    }
+
+    #[test]
+    fn test_log_directories_are_unique_per_process() {
+        let dir = log_directory(".");
+        let name = dir.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.ends_with(&format!("-{}", std::process::id())));
+        assert!(name.contains('.'));
+    }
+
+    #[tokio::test]
+    async fn test_run_all_keeps_order_and_fails_fast() {
+        let dirs: Vec<_> = (0..3).map(|_| tempdir().unwrap()).collect();
+        let repos: Vec<Repository> = dirs
+            .iter()
+            .map(|d| test_repository(d.path().to_str().unwrap()))
+            .collect();
+
+        let results = run_all(repos.clone(), shell("true"), prefixed(), 3, false).await;
+        assert_eq!(results.len(), 3);
+        for (repo, result) in repos.iter().zip(&results) {
+            assert_eq!(repo.name, result.repository);
+            assert!(result.success());
+        }
+
+        let results = run_all(repos, shell("false"), prefixed(), 1, true).await;
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let variables = vec![
+            ("name", "frontend".to_string()),
+            ("org", "acme".to_string()),
+            ("branch", "main".to_string()),
+        ];
+        assert_eq!(
+            expand_placeholders("echo {org}/{name} on {branch} {unknown}", &variables),
+            "echo acme/frontend on main {unknown}"
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("main"), "'main'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[tokio::test]
+    async fn test_shell_placeholders_are_quoted() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let path = temp_dir.path().join("with space");
+        std::fs::create_dir(&path).unwrap();
+        let mut repo = test_repository(path.to_str().unwrap());
+        repo.organization = "acme; exit 7".to_string();
+
+        let result = run_in_repository(
+            &repo,
+            &shell("test -d {path} && test {org} = 'acme; exit 7'"),
+            &prefixed(),
+        )
+        .await;
+        assert!(result.success());
+    }
+
+    #[tokio::test]
+    async fn test_run_in_repository_exports_environment() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let log_dir = tempdir().expect("Failed to create temp dir");
+        let mut repo = test_repository(temp_dir.path().to_str().unwrap());
+        repo.organization = "acme".to_string();
+        repo.env.insert("GREETING".to_string(), "hello".to_string());
+        let options = ExecOptions {
+            log_dir: Some(log_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let result = run_in_repository(
+            &repo,
+            &shell("echo {org} $PALETTE_REPO_ORG $GREETING"),
+            &options,
+        )
+        .await;
+        assert!(result.success());
+
+        let log_name = format!("{}.log", repo.name);
+        let log_file = log_dir
+            .path()
+            .join(Path::new(&log_name).file_name().unwrap());
+        let log = std::fs::read_to_string(log_file).expect("Failed to read log");
+        assert_eq!(log, "acme acme hello\n");
+    }
+
+    #[test]
+    fn test_run_conditions_match_repository_contents() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("Cargo.toml"), "").unwrap();
+        std::fs::write(temp_dir.path().join("src").join("main.rs"), "").unwrap();
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+
+        assert!(RunConditions::default().matches(&repo).unwrap());
+
+        let rust = RunConditions {
+            if_exists: vec!["Cargo.toml".to_string()],
+            if_file_matches: vec!["**/*.rs".to_string()],
+            ..Default::default()
+        };
+        assert!(rust.matches(&repo).unwrap());
+
+        let node = RunConditions {
+            if_exists: vec!["package.json".to_string()],
+            ..Default::default()
+        };
+        assert!(!node.matches(&repo).unwrap());
+
+        let python = RunConditions {
+            if_file_matches: vec!["**/*.py".to_string()],
+            ..Default::default()
+        };
+        assert!(!python.matches(&repo).unwrap());
+
+        // Not a git repository, so the status cannot be determined
+        let dirty = RunConditions {
+            if_dirty: true,
+            ..Default::default()
+        };
+        assert!(dirty.matches(&repo).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_argv_command_bypasses_shell() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let log_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+        let options = ExecOptions {
+            log_dir: Some(log_dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        // Shell syntax is passed through literally as an argument
+        let command = ExecCommand::Argv(vec!["echo".to_string(), "$HOME; exit 1".to_string()]);
+        let result = run_in_repository(&repo, &command, &options).await;
+        assert!(result.success());
+
+        let log_name = format!("{}.log", repo.name);
+        let log_file = log_dir
+            .path()
+            .join(Path::new(&log_name).file_name().unwrap());
+        let log = std::fs::read_to_string(log_file).expect("Failed to read log");
+        assert_eq!(log, "$HOME; exit 1\n");
+    }
+
+    #[tokio::test]
+    async fn test_configured_shell_is_used() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+        let options = ExecOptions {
+            shell: ShellConfig {
+                program: "/bin/sh".to_string(),
+                args: vec!["-e".to_string(), "-c".to_string()],
+            },
+            ..prefixed()
+        };
+
+        // With -e the shell stops at the first failing command
+        let result = run_in_repository(&repo, &shell("false; true"), &options).await;
+        assert!(!result.success());
+    }
+
+    #[tokio::test]
+    async fn test_run_hook_stops_at_first_failure() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let repo = test_repository(temp_dir.path().to_str().unwrap());
+        let shell = ShellConfig::default();
+
+        let commands = vec!["touch first".to_string(), "true".to_string()];
+        run_hook(&repo, Hook::PostPull, &commands, &shell)
+            .await
+            .unwrap();
+        assert!(temp_dir.path().join("first").exists());
+
+        let commands = vec!["exit 2".to_string(), "touch second".to_string()];
+        let result = run_hook(&repo, Hook::PrePush, &commands, &shell).await;
+        assert!(result.unwrap_err().to_string().contains("pre_push"));
+        assert!(!temp_dir.path().join("second").exists());
+    }
+}
@@ -1,2 +1,6 @@
 pub mod config;
+pub mod exec;
+pub mod plugin;
+pub mod progress;
 pub mod repo;
+pub mod workflow;
@@ -1,15 +1,24 @@
-pub mod config;
-pub mod repo;
-use crate::config::{Configuration, Loads, Repository, Saves};
-use crate::repo::{Branches, Manager, Pulls, Pushes};
 use anyhow::Error;
-use clap::{Parser, Subcommand};
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
 use colorize::AnsiColor;
+use palette::config::{CloneOptions, Configuration, Hook, Loads, Repository, Saves};
+use palette::exec::{self, ExecCommand, ExecOptions, OutputStyle, RunConditions};
+use palette::progress::Progress;
+use palette::repo::{
+    BranchInfo, Branches, CommitSummary, Commits, FetchSettings, FetchedRef, Fetches, Manager,
+    Pulls, PushReport, PushSettings, Pushes, RefChange, Remotes, SparseCheckouts, Statuses,
+    SubmoduleState, SubmoduleStatus, Submodules, Tags, Worktrees,
+};
+use palette::{plugin, workflow};
 use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io;
-use std::io::Write;
-use std::process::Command as stdCommand;
+use std::path::PathBuf;
+use std::time::Duration;
 use tokio::fs;
+use tokio::task::JoinSet;
 #[derive(Parser)]
 #[command(name = "palette")]
 #[command(about = "A powerful command-line tool for managing multiple GitHub repositories")]
@@ -20,18 +29,84 @@ use tokio::fs;
 struct Args {
     #[clap(subcommand)]
     command: Command,
+    #[clap(
+        long,
+        global = true,
+        value_delimiter = ',',
+        help = "Only operate on these repositories (comma separated names)"
+    )]
+    repos: Vec<String>,
 }
 #[derive(Subcommand)]
 enum Command {
     #[command(about = "Push changes for repositories that are ahead of remote")]
     #[command(
-        long_about = "Identifies repositories that have commits ahead of their remote and pushes them after user confirmation. Shows a list of repositories to be pushed and prompts for confirmation before proceeding."
+        long_about = "Identifies repositories that have commits ahead of their remote and pushes them after user confirmation, running any configured pre_push hooks first and skipping repositories whose hook fails. Shows the repositories to be pushed with their outgoing commits and prompts for confirmation before proceeding; anything other than yes, including an empty answer, cancels. Use --yes to push without asking, or --interactive to review the commits going out of each repository and decide one repository at a time. Branches are pushed to each repository's push remote, origin unless push_remote is configured, or to the remote --remote names, and a branch without an upstream is set to track the branch it was pushed to. The preview fetches from the remote being pushed to without updating remote-tracking branches, and --force-with-lease overwrites the remote branch only if it is still at the commit last fetched from it, refusing when there is no remote-tracking branch to compare with, and --tags pushes all local tags as well. Every ref sent is reported as accepted or rejected with the remote's reason, followed by a summary, and palette exits with a failure status if any repository could not be pushed completely."
+    )]
+    Push {
+        #[clap(short, long, help = "Push without asking for confirmation")]
+        yes: bool,
+
+        #[clap(
+            short,
+            long,
+            conflicts_with = "yes",
+            help = "Review the outgoing commits and confirm each repository separately"
+        )]
+        interactive: bool,
+
+        #[clap(
+            long,
+            help = "Overwrite the remote branch if it is still where it was last fetched"
+        )]
+        force_with_lease: bool,
+
+        #[clap(
+            long,
+            help = "Remote to push to instead of each repository's push remote"
+        )]
+        remote: Option<String>,
+
+        #[clap(long, help = "Also push all local tags")]
+        tags: bool,
+    },
+
+    #[command(about = "Show the commits each repository would push")]
+    #[command(
+        long_about = "Fetch the current branch from the push remote, origin unless configured otherwise, in each selected repository and list the local commits the remote does not have yet, with their short hash, subject and author. For a branch that is not on the remote yet every commit missing from the remote's branches is listed. Nothing is pushed."
+    )]
+    Outgoing {},
+
+    #[command(about = "Show what pulling would bring in, without merging")]
+    #[command(
+        long_about = "Fetch the current branch from the pull remote, origin unless configured otherwise, in each selected repository and list the commits the remote has that the local branch does not, followed by the files they change and the number of lines added and removed. The working tree and local branches are left untouched, so you can decide when to pull."
+    )]
+    Incoming {},
+
+    #[command(about = "Refresh remote-tracking branches without merging")]
+    #[command(
+        long_about = "Fetch every selected repository from its pull and push remotes, origin unless configured otherwise, in parallel, updating remote-tracking branches and tags while leaving local branches and working trees untouched, and report the refs that were created, moved or deleted. --prune removes remote-tracking branches that no longer exist on the remote, --tags fetches every tag and --all-remotes fetches from each configured remote. Afterwards list and branches work from the fresh data without going back to the network."
     )]
-    Push {},
+    Fetch {
+        #[clap(
+            long,
+            help = "Delete remote-tracking branches that are gone from the remote"
+        )]
+        prune: bool,
+
+        #[clap(long, help = "Fetch all tags")]
+        tags: bool,
+
+        #[clap(
+            long,
+            help = "Fetch from every remote, not only the pull and push remotes"
+        )]
+        all_remotes: bool,
+    },
 
     #[command(about = "Pull latest changes for repositories")]
     #[command(
-        long_about = "Pull latest changes for all tracked repositories or a specific repository. Automatically clones missing repositories during bulk pull operations and updates configuration with latest checkout information."
+        long_about = "Pull latest changes for all tracked repositories or a specific repository. Automatically clones missing repositories during bulk pull operations and updates configuration with latest checkout information. Sparse paths are applied again after pulling, and configured post_pull and post_clone hooks run afterwards."
     )]
     Pull {
         #[clap(short, long, help = "Name of a specific repository to pull")]
@@ -40,13 +115,16 @@ enum Command {
 
     #[command(about = "List all tracked repositories with their status")]
     #[command(
-        long_about = "Display a comprehensive overview of all tracked repositories showing current branch, commit hash (first 8 characters), whether the local branch is ahead of remote, and checkout status with color-coded output."
+        long_about = "Display a comprehensive overview of all tracked repositories showing current branch, commit hash (first 8 characters), how far the local branch is ahead of and behind its remote-tracking branch, checkout status and how long ago the repository was last fetched, with color-coded output. The comparison uses the remote-tracking branches already stored locally, so list works offline; use --fetch to refresh them first, or run palette fetch."
     )]
-    List {},
+    List {
+        #[clap(long, help = "Fetch every repository before comparing")]
+        fetch: bool,
+    },
 
     #[command(about = "Switch all repositories to a specific branch")]
     #[command(
-        long_about = "Switch all tracked repositories to the specified branch. Optionally create new branches when --create is used. Updates configuration with new checkout information."
+        long_about = "Switch all tracked repositories to the specified branch. Optionally create new branches when --create is used. Updates configuration with new checkout information. Configured post_switch hooks run afterwards."
     )]
     Switch {
         #[clap(short, long, help = "Name of the branch to switch to")]
@@ -55,9 +133,79 @@ enum Command {
         create: Option<bool>,
     },
 
+    #[command(about = "Delete, rename or prune local branches across repositories")]
+    #[command(
+        long_about = "Manage the lifecycle of branches in the selected repositories. Branches can be deleted or renamed everywhere at once, and branches that are fully merged into the default branch can be pruned, optionally removing the matching remote branches after confirmation."
+    )]
+    Branch {
+        #[clap(subcommand)]
+        action: BranchAction,
+    },
+
+    #[command(about = "Show matching branches across repositories")]
+    #[command(
+        long_about = "List local and remote branches whose name matches a glob pattern in each selected repository, with the last commit date, author and how far each branch is ahead of or behind the default branch. Shown as a branch by repository matrix, a detailed list or JSON."
+    )]
+    Branches {
+        #[clap(
+            default_value = "*",
+            help = "Glob pattern to match branch names against"
+        )]
+        pattern: String,
+        #[clap(short, long, value_enum, default_value_t = BranchesFormat::Matrix, help = "How to display the branches")]
+        format: BranchesFormat,
+    },
+
+    #[command(about = "Create or delete a release tag across repositories")]
+    #[command(
+        long_about = "Create an annotated tag at HEAD in each selected repository, optionally signed with your gpg key and pushed to the push remote, origin unless configured otherwise. With --delete the tag is removed locally and, when combined with --push, from the remote as well. Use --dry-run to preview what would happen."
+    )]
+    TagRelease {
+        #[clap(help = "Name of the tag, for example v1.2.0")]
+        name: String,
+        #[clap(short, long, help = "Tag message, defaults to \"Release <name>\"")]
+        message: Option<String>,
+        #[clap(short, long, help = "Create a gpg signed tag")]
+        sign: bool,
+        #[clap(short, long, help = "Push the tag to the push remote")]
+        push: bool,
+        #[clap(short, long, help = "Delete the tag instead of creating it")]
+        delete: bool,
+        #[clap(long, help = "Show what would be done without changing anything")]
+        dry_run: bool,
+    },
+
+    #[command(about = "List tags matching a pattern across repositories")]
+    #[command(
+        long_about = "List the tags whose name matches a glob pattern in each selected repository, with the commit each tag points at."
+    )]
+    Tags {
+        #[clap(default_value = "*", help = "Glob pattern to match tag names against")]
+        pattern: String,
+    },
+
+    #[command(about = "Commit pending changes in every dirty repository")]
+    #[command(
+        long_about = "Commit in each selected repository using your configured git identity. Like git commit, only what is already staged is committed; --all first stages every change, modified and deleted tracked files as well as untracked files. The message may contain {repo}, which is replaced with the repository name. Repositories with nothing to commit are reported and skipped."
+    )]
+    Commit {
+        #[clap(
+            short,
+            long,
+            help = "Commit message, {repo} is replaced with the repository name"
+        )]
+        message: String,
+        #[clap(
+            short,
+            long,
+            help = "Stage all changes, including untracked files, before committing"
+        )]
+        all: bool,
+    },
+
     #[command(about = "Add a new repository to track and clone it")]
     #[command(
-        long_about = "Add a new repository to the configuration file and clone it locally. The repository will be tracked and included in future bulk operations."
+        long_about = "Add a new repository to the configuration file and clone it locally. The repository will be tracked and included in future bulk operations. The clone can be limited to recent history with --depth, to one branch with --single-branch and --branch, or made partial with --filter; these options are saved and used again whenever the repository has to be cloned. With --recurse-submodules, submodules are initialised and updated after every clone and pull."
     )]
     Add {
         #[clap(
@@ -68,14 +216,123 @@ enum Command {
         organization: String,
         #[clap(short, long, help = "Name of the repository to add")]
         name: String,
+        #[clap(long, help = "Only clone this many commits of history")]
+        depth: Option<u32>,
+        #[clap(long, help = "Only clone the branch that is checked out")]
+        single_branch: bool,
+        #[clap(long, help = "Branch to check out instead of the default branch")]
+        branch: Option<String>,
+        #[clap(
+            long,
+            help = "Partial clone filter such as blob:none, cloned with the git command line"
+        )]
+        filter: Option<String>,
+        #[clap(
+            long,
+            help = "Initialise and update submodules after cloning and pulling"
+        )]
+        recurse_submodules: bool,
+    },
+
+    #[command(about = "Add or remove sparse checkout directories across repositories")]
+    #[command(
+        long_about = "Change the directories checked out in the selected repositories. The directories are saved as sparse_paths in config.palette and applied with git sparse-checkout in cone mode, so files at the top of the repository are always present. Removing the last directory checks the whole repository out again. Sparse paths are also applied after a clone and after every pull."
+    )]
+    Sparse {
+        #[clap(subcommand)]
+        action: SparseAction,
+    },
+
+    #[command(about = "Fetch the history missing from shallow clones")]
+    #[command(
+        long_about = "Fetch the history that shallow clones of the selected repositories are missing, either all of it or, with --depth, enough for that many commits. Repositories that are not shallow are reported and left alone."
+    )]
+    Unshallow {
+        #[clap(
+            long,
+            help = "Deepen to this many commits instead of fetching everything"
+        )]
+        depth: Option<u32>,
+    },
+
+    #[command(about = "Fast-forward the default branch of forks from upstream")]
+    #[command(
+        long_about = "For repositories that pull from one remote and push to another, such as a fork with an upstream remote, fetch the default branch from the pull remote and push it to the push remote, then fast-forward the local default branch as well, with a warning if local changes are in the way. Nothing is merged: a fork whose default branch has commits of its own is reported and left alone."
+    )]
+    SyncFork {},
+
+    #[command(about = "Manage parallel workspaces of git worktrees")]
+    #[command(
+        long_about = "Check a branch out in a git worktree of every selected repository, side by side in another directory, so work on it can happen without touching the current checkouts. The directory gets its own config.palette listing the worktrees and sharing this workspace's shell, commands and hooks, so palette can be run from there as usual. Missing branches are created from the pull remote's branch of the same name, origin unless pull_remote is configured, or from HEAD."
+    )]
+    Worktree {
+        #[clap(subcommand)]
+        action: WorktreeAction,
     },
     #[command(about = "Execute an arbitrary command in all repositories")]
     #[command(
-        long_about = "Execute the specified command in each tracked repository's directory. Displays output from all repositories sequentially. Useful for running checks, builds, or any command across your entire repository collection."
+        long_about = "Execute the specified command in each tracked repository's directory. The command is run by the shell configured in config.palette (/bin/sh by default), or given after -- to run a program directly with no shell involved. Output from every repository is streamed as it is produced, shown under a header or with each line prefixed by the repository name, and stderr is passed through. Commands can be run in several repositories at once and killed after a timeout. The output of each run is saved under .palette/logs/<timestamp>-<pid>/<repo>.log. The placeholders {name}, {org}, {branch}, {sha} and {path} are replaced with each repository's values, single quoted when the command goes through the shell, which are also exported as PALETTE_REPO_NAME, PALETTE_REPO_ORG, PALETTE_REPO_BRANCH, PALETTE_REPO_SHA and PALETTE_REPO_PATH along with the repository's configured env. The --if-* options restrict the command to repositories containing certain files, with uncommitted changes or with commits ahead of their upstream. A pass/fail summary is printed at the end and palette exits with a failure status if the command failed anywhere. Useful for running checks, builds, or any command across your entire repository collection."
     )]
     Exec {
-        #[clap(short, long, help = "Command to execute in all tracked repositories")]
-        run_command: String,
+        #[clap(
+            short,
+            long,
+            required_unless_present = "argv",
+            conflicts_with = "argv",
+            help = "Command to execute in all tracked repositories, may use {name}, {org}, {branch}, {sha} and {path}"
+        )]
+        run_command: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = OutputStyle::Sectioned, help = "How to label the output of each repository")]
+        output: OutputStyle,
+        #[clap(
+            short,
+            long,
+            help = "Stop at the first repository where the command fails"
+        )]
+        fail_fast: bool,
+        #[clap(
+            short,
+            long,
+            help = "Kill the command if it runs longer than this many seconds"
+        )]
+        timeout: Option<u64>,
+        #[clap(
+            short,
+            long,
+            default_value_t = 1,
+            help = "Number of repositories to run in at the same time, output is prefixed when above 1"
+        )]
+        parallel: usize,
+        #[clap(
+            long,
+            help = "Only run in repositories where this path exists, relative to the repository root"
+        )]
+        if_exists: Vec<String>,
+        #[clap(
+            long,
+            help = "Only run in repositories containing a file matching this glob, for example '**/*.py'"
+        )]
+        if_file_matches: Vec<String>,
+        #[clap(long, help = "Only run in repositories with uncommitted changes")]
+        if_dirty: bool,
+        #[clap(
+            long,
+            help = "Only run in repositories with commits ahead of their upstream"
+        )]
+        if_ahead: bool,
+        #[clap(
+            last = true,
+            help = "Program and arguments to run directly without a shell, given after --"
+        )]
+        argv: Vec<String>,
+    },
+    #[command(about = "Run a command defined in the configuration")]
+    #[command(
+        long_about = "Run an alias or multi-step workflow from the commands section of config.palette. Each step is either a palette subcommand or a shell command line run in the selected repositories, and decides whether the workflow stops or continues when it fails. Palette exits with a failure status if any step failed. Without a name the available commands are listed."
+    )]
+    Run {
+        #[clap(help = "Name of the command to run")]
+        name: Option<String>,
     },
     #[command(about = "Remove a repository from tracking")]
     #[command(
@@ -85,13 +342,102 @@ enum Command {
         #[clap(short, long, help = "Name of the repository to remove from tracking")]
         name: String,
     },
+    /// Any other subcommand is dispatched to a `palette-<name>` executable on PATH
+    #[command(external_subcommand)]
+    External(Vec<String>),
+}
+
+#[derive(Subcommand)]
+enum BranchAction {
+    #[command(about = "Delete a local branch in every repository that has it")]
+    Delete {
+        #[clap(help = "Name of the branch to delete")]
+        name: String,
+        #[clap(
+            short,
+            long,
+            help = "Delete the branch even if it is not merged into the default branch"
+        )]
+        force: bool,
+    },
+    #[command(about = "Rename a local branch in every repository that has it")]
+    Rename {
+        #[clap(help = "Current name of the branch")]
+        old_name: String,
+        #[clap(help = "New name for the branch")]
+        new_name: String,
+        #[clap(short, long, help = "Overwrite an existing branch with the new name")]
+        force: bool,
+    },
+    #[command(about = "Delete local branches fully merged into the default branch")]
+    PruneMerged {
+        #[clap(
+            short,
+            long,
+            help = "Also delete the matching branches on the push remote after confirmation, if they are merged as well"
+        )]
+        remote: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SparseAction {
+    #[command(about = "Add directories to the sparse checkout of every selected repository")]
+    Add {
+        #[clap(required = true, help = "Directories to check out")]
+        paths: Vec<String>,
+    },
+    #[command(about = "Remove directories from the sparse checkout of every selected repository")]
+    Remove {
+        #[clap(required = true, help = "Directories to stop checking out")]
+        paths: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WorktreeAction {
+    #[command(
+        about = "Create a workspace in <dir> with <branch> checked out in every selected repository"
+    )]
+    Add {
+        #[clap(help = "Branch to check out, created when it does not exist")]
+        branch: String,
+        #[clap(help = "Directory of the new workspace")]
+        dir: PathBuf,
+    },
+    #[command(about = "List the worktrees of every selected repository")]
+    List {},
+    #[command(about = "Remove the worktrees of a workspace created with worktree add")]
+    Remove {
+        #[clap(help = "Directory of the workspace")]
+        dir: PathBuf,
+        #[clap(
+            short,
+            long,
+            help = "Remove worktrees even if they have uncommitted changes"
+        )]
+        force: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BranchesFormat {
+    Matrix,
+    List,
+    Json,
+}
+
+#[derive(Serialize)]
+struct RepositoryBranches {
+    repository: String,
+    branches: Vec<BranchInfo>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     env_logger::init();
 
-    let repo_manager = Manager::default();
+    let repo_manager = Manager::with_progress(Progress::default());
     let mut configuration_manager = Configuration::default();
     // Load configuration if it is already present
     configuration_manager
@@ -99,163 +445,991 @@ async fn main() -> Result<(), Error> {
         .await
         .expect("Could not load configuration, something went wrong!");
     let args = Args::parse();
+    let selected = configuration_manager.select_repositories(&args.repos);
 
     match args.command {
-        Command::Push { .. } => {
+        Command::Push {
+            yes,
+            interactive,
+            force_with_lease,
+            remote,
+            tags,
+        } => {
+            // The preview fetches from the remote being pushed to, without touching the
+            // tracking refs a lease is checked against
+            let push_remote =
+                |repo: &Repository| remote.clone().unwrap_or_else(|| repo.push_to().to_string());
             // List each repo commits locally vs the remote
             let mut candidate_updates = vec![];
-            for repo in configuration_manager.get_repository() {
-                if let Ok((is_different, commit)) = repo_manager.compare(repo) {
+            for &repo in &selected {
+                if let Ok((is_different, commit)) = repo_manager.compare(repo, &push_remote(repo)) {
                     println!("{}: {} ", repo.name, commit);
                     if is_different {
                         candidate_updates.push(repo);
                     }
                 }
             }
-            println!("Please confirm that you wish to push repositories [y/N]");
-            // confirm action
-            let mut input: String = String::new(); // Create a string variable
-            io::stdin() // Get the standard input stream
-                .read_line(&mut input) // The read_line function reads data until it reaches a '\n' character
-                .expect("Unable to read Stdin"); // In case the read operation fails, it panics with the given message
-            let re = Regex::new("(?i)^n").unwrap(); // (?i) = case-insensitive, ^n = starts with 'n'
-            if re.is_match(&input) {
+            if candidate_updates.is_empty() {
+                println!("Nothing to push");
                 return Ok(());
             }
 
+            if interactive {
+                let mut chosen = vec![];
+                for repo in candidate_updates {
+                    print_outgoing(&repo_manager, repo, &push_remote(repo));
+                    if confirm(&format!("Push {}? [y/N]", repo.name))? {
+                        chosen.push(repo);
+                    }
+                }
+                candidate_updates = chosen;
+            } else {
+                for &repo in &candidate_updates {
+                    print_outgoing(&repo_manager, repo, &push_remote(repo));
+                }
+                if !yes && !confirm("Please confirm that you wish to push repositories [y/N]")? {
+                    return Ok(());
+                }
+            }
+
+            let mut outcomes = vec![];
             for repo in candidate_updates {
+                let commands = configuration_manager.hook_commands(repo, Hook::PrePush);
+                if let Err(e) =
+                    exec::run_hook(repo, Hook::PrePush, &commands, &configuration_manager.shell)
+                        .await
+                {
+                    println!("{}: {}", repo.name, e.to_string().red());
+                    outcomes.push((repo.name.as_str(), Err(e)));
+                    continue;
+                }
                 println!("Pushing {}", repo.name);
-                let repo_manager = repo_manager.clone();
-                let name = repo.name.clone();
-                tokio::spawn(async move {
-                    repo_manager.push(name).await.expect("Repo Manager");
-                });
+                let settings = PushSettings {
+                    remote: push_remote(repo),
+                    force_with_lease,
+                    tags,
+                };
+                let result = repo_manager.push(repo.name.clone(), &settings).await;
+                match &result {
+                    Ok(report) => print_push_report(&repo.name, report),
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+                outcomes.push((repo.name.as_str(), result));
+            }
+            print_push_summary(&outcomes);
+            if outcomes
+                .iter()
+                .any(|(_, result)| !result.as_ref().is_ok_and(PushReport::accepted))
+            {
+                std::process::exit(1);
+            }
+        }
+        Command::Outgoing { .. } => {
+            for &repo in &selected {
+                print_outgoing(&repo_manager, repo, repo.push_to());
+            }
+        }
+        Command::Incoming { .. } => {
+            for &repo in &selected {
+                match repo_manager.incoming(repo) {
+                    Ok(incoming) if incoming.commits.is_empty() => {
+                        println!("{}: up to date", repo.name)
+                    }
+                    Ok(incoming) => {
+                        println!(
+                            "{}: {} incoming commit(s), {} file(s) changed, {} {}",
+                            repo.name.clone().bold(),
+                            incoming.commits.len(),
+                            incoming.files.len(),
+                            format!("+{}", incoming.insertions).green(),
+                            format!("-{}", incoming.deletions).red()
+                        );
+                        print_commits(&incoming.commits);
+                        for file in &incoming.files {
+                            println!("    {} {}", file.status, file.path);
+                        }
+                    }
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+        }
+        Command::Fetch {
+            prune,
+            tags,
+            all_remotes,
+        } => {
+            let settings = FetchSettings {
+                prune,
+                tags,
+                all_remotes,
+            };
+            let mut failed = false;
+            for (name, result) in fetch_all(&selected, &repo_manager, &settings).await {
+                match result {
+                    Ok(fetched) if fetched.is_empty() => println!("{name}: up to date"),
+                    Ok(fetched) => print_fetched(&name, &fetched),
+                    Err(e) => {
+                        failed = true;
+                        println!("{}: {}", name, e.to_string().red());
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(1);
             }
-            // push
         }
         Command::Switch {
             branch_name,
             create,
         } => {
-            for repo in configuration_manager.get_repository() {
-                let checkoutInfo = repo_manager.change_branch(
+            for &repo in &selected {
+                let checkout_info = repo_manager.change_branch(
                     branch_name.as_str(),
                     repo,
-                    if create.is_some() {
-                        create.unwrap()
-                    } else {
-                        false
-                    },
+                    create.unwrap_or(false),
                 )?;
                 println!(
                     "{} switched branch to {}",
-                    repo.name, checkoutInfo.branch_name
-                )
+                    repo.name, checkout_info.branch_name
+                );
+                run_post_hook(&configuration_manager, repo, Hook::PostSwitch).await;
             }
         }
         Command::Pull { name } => {
             if let Some(name) = name {
-                // This is a hack to pass through the right data structure, but all we need is the name
-                let mut repository = Repository::default();
-                repository.name = name;
+                // Prefer the configured entry so its hooks apply, all update needs is the name
+                let repository = configuration_manager
+                    .get_repository()
+                    .iter()
+                    .find(|r| r.name == name)
+                    .cloned()
+                    .unwrap_or(Repository {
+                        name,
+                        ..Default::default()
+                    });
+                ensure_remotes(&repo_manager, &repository);
                 repo_manager.update(&repository)?;
+                update_submodules(&repo_manager, &repository);
+                apply_sparse_paths(&repo_manager, &repository);
+                run_post_hook(&configuration_manager, &repository, Hook::PostPull).await;
                 return Ok(());
             }
             // get each repo and update
-            for repo in configuration_manager.get_repository() {
+            for &repo in &selected {
                 // If the repository doesn't exist, clone instead
                 // this is a nice to have to keep palette in sync
                 let repo = repo.clone();
-                if let Ok(exists) = fs::try_exists(repo.name.clone()).await {
-                    if !exists {
-                        println!("Repository {} was missing, fetching...", repo.name);
-                        add_repo(
-                            repo.organization,
-                            repo.name,
-                            configuration_manager.clone(),
-                            repo_manager.clone(),
-                            false,
-                        )
-                        .await?;
-                        continue;
-                    }
+                if let Ok(exists) = fs::try_exists(repo.name.clone()).await
+                    && !exists
+                {
+                    println!("Repository {} was missing, fetching...", repo.name);
+                    add_repo(
+                        repo,
+                        configuration_manager.clone(),
+                        repo_manager.clone(),
+                        false,
+                    )
+                    .await?;
+                    continue;
                 }
-                repo_manager.update(&repo)?
+                ensure_remotes(&repo_manager, &repo);
+                repo_manager.update(&repo)?;
+                update_submodules(&repo_manager, &repo);
+                apply_sparse_paths(&repo_manager, &repo);
+                run_post_hook(&configuration_manager, &repo, Hook::PostPull).await;
             }
         }
-        Command::List { .. } => {
-            for repo in configuration_manager.get_repository() {
-                if let Ok((is_different, commit)) = repo_manager.compare(repo) {
-                    if is_different {
+        Command::List { fetch } => {
+            let mut fetch_errors = BTreeMap::new();
+            if fetch {
+                for (name, result) in
+                    fetch_all(&selected, &repo_manager, &FetchSettings::default()).await
+                {
+                    if let Err(e) = result {
+                        fetch_errors.insert(name, e);
+                    }
+                }
+            }
+            let now = Utc::now();
+            for &repo in &selected {
+                if let Some(e) = fetch_errors.get(&repo.name) {
+                    println!("{}: fetch failed, {}", repo.name, e.to_string().red());
+                }
+                let status = match repo_manager.status(repo) {
+                    Ok(status) => status,
+                    Err(_) if !repo.cloned_locally => {
                         println!(
-                            "{} branch:{}, commit:{:.8}, ahead of remote: {}, checked out: {}",
+                            "{} branch:{}, commit:{:.8}, checked out: {}",
                             repo.name,
                             repo.checkout_info.branch_name,
                             repo.checkout_info.commit_sha.as_str(),
-                            "yes".yellow(),
-                            if repo.cloned_locally {
-                                "yes".green()
+                            "no".red()
+                        );
+                        continue;
+                    }
+                    Err(e) => {
+                        println!("{}: {}", repo.name, e.to_string().red());
+                        continue;
+                    }
+                };
+                let compared = |count: usize| match (&status.upstream, count) {
+                    (None, _) => "no upstream".yellow(),
+                    (Some(_), 0) => "no".green(),
+                    (Some(_), n) => format!("yes ({n})").yellow(),
+                };
+                println!(
+                    "{} branch:{}, commit:{:.8}, ahead of remote: {}, behind remote: {}, checked out: {}, last fetch: {}{}",
+                    repo.name,
+                    status.branch_name,
+                    status.commit_sha,
+                    compared(status.ahead),
+                    compared(status.behind),
+                    "yes".green(),
+                    status
+                        .last_fetched
+                        .map(|at| time_ago(now - at))
+                        .unwrap_or_else(|| "never".to_string()),
+                    describe_submodules(&status.submodules)
+                );
+            }
+        }
+        Command::Branch { action } => match action {
+            BranchAction::Delete { name, force } => {
+                for &repo in &selected {
+                    match repo_manager.delete_branch(repo, &name, force) {
+                        Ok(()) => println!("{}: deleted branch '{}'", repo.name, name),
+                        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                    }
+                }
+            }
+            BranchAction::Rename {
+                old_name,
+                new_name,
+                force,
+            } => {
+                for &repo in &selected {
+                    match repo_manager.rename_branch(repo, &old_name, &new_name, force) {
+                        Ok(()) => println!(
+                            "{}: renamed branch '{}' to '{}'",
+                            repo.name, old_name, new_name
+                        ),
+                        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                    }
+                }
+            }
+            BranchAction::PruneMerged { remote } => {
+                for &repo in &selected {
+                    let merged = match repo_manager.merged_branches(repo) {
+                        Ok(merged) => merged,
+                        Err(e) => {
+                            println!("{}: {}", repo.name, e.to_string().red());
+                            continue;
+                        }
+                    };
+                    if merged.is_empty() {
+                        println!("{}: no merged branches to prune", repo.name);
+                        continue;
+                    }
+                    // Only offer remote branches whose local branch went and whose own
+                    // commits are all merged too
+                    let mut remote_merged = vec![];
+                    for branch in &merged {
+                        match repo_manager.delete_branch(repo, branch, false) {
+                            Ok(()) => {
+                                println!("{}: deleted merged branch '{}'", repo.name, branch);
+                                if !remote {
+                                    continue;
+                                }
+                                match repo_manager.remote_branch_merged(repo, branch) {
+                                    Ok(true) => remote_merged.push(branch.as_str()),
+                                    Ok(false) => {}
+                                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                                }
+                            }
+                            Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                        }
+                    }
+                    if !remote_merged.is_empty()
+                        && confirm(&format!(
+                            "Delete {} from {} in {}? [y/N]",
+                            remote_merged.join(", "),
+                            repo.push_to(),
+                            repo.name
+                        ))?
+                    {
+                        for branch in remote_merged {
+                            match repo_manager.delete_remote_branch(repo, branch) {
+                                Ok(()) => println!(
+                                    "{}: deleted remote branch '{}/{}'",
+                                    repo.name,
+                                    repo.push_to(),
+                                    branch
+                                ),
+                                Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        Command::Branches { pattern, format } => {
+            let pattern = glob::Pattern::new(&pattern)?;
+            let mut overview = vec![];
+            for &repo in &selected {
+                match repo_manager.list_branches(repo, &pattern) {
+                    Ok(branches) => overview.push(RepositoryBranches {
+                        repository: repo.name.clone(),
+                        branches,
+                    }),
+                    Err(e) => eprintln!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+            match format {
+                BranchesFormat::Json => println!("{}", serde_json::to_string_pretty(&overview)?),
+                BranchesFormat::List => print_branch_list(&overview),
+                BranchesFormat::Matrix => print_branch_matrix(&overview),
+            }
+        }
+        Command::TagRelease {
+            name,
+            message,
+            sign,
+            push,
+            delete,
+            dry_run,
+        } => {
+            let message = message.unwrap_or_else(|| format!("Release {name}"));
+            let exact = glob::Pattern::new(&glob::Pattern::escape(&name))?;
+            for &repo in &selected {
+                if dry_run {
+                    let preview = repo_manager
+                        .list_tags(repo, &exact)
+                        .and_then(|existing| Ok((existing, repo_manager.current_checkout(repo)?)));
+                    let (existing, head) = match preview {
+                        Ok(preview) => preview,
+                        Err(e) => {
+                            println!("{}: {}", repo.name, e.to_string().red());
+                            continue;
+                        }
+                    };
+                    match (delete, existing.first()) {
+                        (true, Some(tag)) => println!(
+                            "{}: would delete tag '{}' at {:.8}{}",
+                            repo.name,
+                            name,
+                            tag.commit_sha,
+                            if push {
+                                format!(" and remove it from {}", repo.push_to())
                             } else {
-                                "no".red()
+                                String::new()
                             }
-                        );
-                    } else {
-                        println!(
-                            "{} branch:{}, commit:{:.8}, ahead of remote: {}, checked out: {}",
+                        ),
+                        (true, None) => println!("{}: tag '{}' does not exist", repo.name, name),
+                        (false, Some(tag)) => println!(
+                            "{}: tag '{}' already exists at {:.8}",
+                            repo.name, name, tag.commit_sha
+                        ),
+                        (false, None) => println!(
+                            "{}: would create {}tag '{}' at {:.8} on {}{}",
                             repo.name,
-                            repo.checkout_info.branch_name,
-                            repo.checkout_info.commit_sha.as_str(),
-                            "no".green(),
-                            if repo.cloned_locally {
-                                "yes".green()
+                            if sign { "signed " } else { "" },
+                            name,
+                            head.commit_sha,
+                            head.branch_name,
+                            if push {
+                                format!(" and push it to {}", repo.push_to())
                             } else {
-                                "no".red()
+                                String::new()
                             }
-                        );
+                        ),
+                    }
+                    continue;
+                }
+
+                if delete {
+                    match repo_manager.delete_tag(repo, &name, push) {
+                        Ok(()) => println!("{}: deleted tag '{}'", repo.name, name),
+                        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                    }
+                    continue;
+                }
+                if let Err(e) = repo_manager.create_tag(repo, &name, &message, sign) {
+                    println!("{}: {}", repo.name, e.to_string().red());
+                    continue;
+                }
+                println!("{}: created tag '{}'", repo.name, name);
+                if push {
+                    match repo_manager.push_tag(repo, &name) {
+                        Ok(()) => {
+                            println!("{}: pushed tag '{}' to {}", repo.name, name, repo.push_to())
+                        }
+                        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
                     }
                 }
             }
         }
-        Command::Add { organization, name } => {
+        Command::Tags { pattern } => {
+            let pattern = glob::Pattern::new(&pattern)?;
+            for &repo in &selected {
+                match repo_manager.list_tags(repo, &pattern) {
+                    Ok(tags) => {
+                        for tag in tags {
+                            println!(
+                                "{} {} commit:{:.8}{}",
+                                repo.name,
+                                tag.name,
+                                tag.commit_sha,
+                                if tag.annotated { "" } else { " (lightweight)" }
+                            );
+                        }
+                    }
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+        }
+        Command::Commit { message, all } => {
+            let mut unchanged = vec![];
+            for &repo in &selected {
+                let message = message.replace("{repo}", &repo.name);
+                match repo_manager.commit_changes(repo, &message, all) {
+                    Ok(Some(commit)) => println!("{}: committed {:.8}", repo.name, commit),
+                    Ok(None) => unchanged.push(repo.name.as_str()),
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+            if !unchanged.is_empty() {
+                println!("Nothing to commit in: {}", unchanged.join(", "));
+            }
+        }
+        Command::Add {
+            organization,
+            name,
+            depth,
+            single_branch,
+            branch,
+            filter,
+            recurse_submodules,
+        } => {
+            let clone_options = CloneOptions {
+                depth,
+                single_branch,
+                branch,
+                filter,
+            };
             add_repo(
-                organization,
-                name,
+                Repository {
+                    name,
+                    organization,
+                    clone_options,
+                    recurse_submodules,
+                    ..Default::default()
+                },
                 configuration_manager,
                 repo_manager,
                 true,
             )
             .await?;
         }
-        Command::Exec { run_command } => {
+        Command::Sparse { action } => {
+            let names: Vec<String> = selected.iter().map(|r| r.name.clone()).collect();
+            for name in &names {
+                let repo = configuration_manager.get_repository_mut(name.clone());
+                match &action {
+                    SparseAction::Add { paths } => {
+                        for path in paths {
+                            if !repo.sparse_paths.contains(path) {
+                                repo.sparse_paths.push(path.clone());
+                            }
+                        }
+                    }
+                    SparseAction::Remove { paths } => {
+                        repo.sparse_paths.retain(|path| !paths.contains(path))
+                    }
+                }
+            }
+            configuration_manager.save().await?;
+
             for repo in configuration_manager.get_repository() {
-                // use repo name as our path
-                let path = repo.clone().name;
-                let output = stdCommand::new("/bin/sh")
-                    .arg("-c")
-                    .arg(run_command.clone())
-                    .current_dir(path)
-                    .output()?;
-                io::stdout().write_all(&output.stdout)?;
+                if !names.contains(&repo.name) {
+                    continue;
+                }
+                match repo_manager.apply_sparse_paths(repo) {
+                    Ok(()) if repo.sparse_paths.is_empty() => {
+                        println!("{}: checking out everything", repo.name)
+                    }
+                    Ok(()) => println!(
+                        "{}: checking out {}",
+                        repo.name,
+                        repo.sparse_paths.join(", ")
+                    ),
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+        }
+        Command::Unshallow { depth } => {
+            for &repo in &selected {
+                match repo_manager.unshallow(repo, depth) {
+                    Ok(()) => println!("{}: fetched missing history", repo.name),
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+        }
+        Command::SyncFork {} => {
+            for &repo in &selected {
+                match repo_manager.sync_fork(repo) {
+                    Ok(synced) => {
+                        if synced.updated {
+                            println!(
+                                "{}: {}/{} fast-forwarded to {:.8} from {}",
+                                repo.name,
+                                repo.push_to(),
+                                synced.branch,
+                                synced.commit_sha,
+                                repo.pull_from()
+                            );
+                        } else {
+                            println!(
+                                "{}: {}/{} already up to date with {}",
+                                repo.name,
+                                repo.push_to(),
+                                synced.branch,
+                                repo.pull_from()
+                            );
+                        }
+                        if let Some(warning) = synced.local_warning {
+                            println!("{}: {}", repo.name, warning.yellow());
+                        }
+                    }
+                    Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                }
+            }
+        }
+        Command::Worktree { action } => match action {
+            WorktreeAction::Add { branch, dir } => {
+                let dir = std::path::absolute(&dir)?;
+                let config_path = dir.join(&configuration_manager.configuration_file_name);
+                if config_path.exists() {
+                    return Err(anyhow::anyhow!(
+                        "{} already has a {}",
+                        dir.display(),
+                        configuration_manager.configuration_file_name
+                    ));
+                }
+
+                let mut workspace = configuration_manager.workspace();
+                for &repo in &selected {
+                    let path = dir.join(&repo.name);
+                    match repo_manager.add_worktree(repo, &branch, &path) {
+                        Ok(checkout_info) => {
+                            println!(
+                                "{}: {} checked out in {}",
+                                repo.name,
+                                branch,
+                                path.display()
+                            );
+                            let mut worktree_repo = repo.clone();
+                            worktree_repo.cloned_locally = true;
+                            worktree_repo.checkout_info = checkout_info;
+                            if !worktree_repo.sparse_paths.is_empty() {
+                                apply_sparse_paths(
+                                    &repo_manager,
+                                    &Repository {
+                                        name: path.to_string_lossy().to_string(),
+                                        ..worktree_repo.clone()
+                                    },
+                                );
+                            }
+                            workspace.add_repository(worktree_repo);
+                        }
+                        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                    }
+                }
+                if workspace.get_repository().is_empty() {
+                    return Ok(());
+                }
+                fs::write(&config_path, serde_json::to_string_pretty(&workspace)?).await?;
+                println!(
+                    "Workspace for {} is ready, run palette in {} to work on it",
+                    branch,
+                    dir.display()
+                );
+            }
+            WorktreeAction::List {} => {
+                for &repo in &selected {
+                    match repo_manager.list_worktrees(repo) {
+                        Ok(worktrees) if worktrees.is_empty() => {}
+                        Ok(worktrees) => {
+                            println!("{}", repo.name.clone().bold());
+                            for worktree in worktrees {
+                                println!(
+                                    "  {} {} ({})",
+                                    worktree.path.display(),
+                                    worktree.branch.unwrap_or_else(|| "detached".to_string()),
+                                    worktree.name
+                                );
+                            }
+                        }
+                        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+                    }
+                }
+            }
+            WorktreeAction::Remove { dir, force } => {
+                let dir = std::path::absolute(&dir)?;
+                let mut removed_all = true;
+                for &repo in &selected {
+                    let path = dir.join(&repo.name);
+                    if !path.exists() {
+                        continue;
+                    }
+                    match repo_manager.remove_worktree(repo, &path, force) {
+                        Ok(()) => println!("{}: removed {}", repo.name, path.display()),
+                        Err(e) => {
+                            removed_all = false;
+                            println!("{}: {}", repo.name, e.to_string().red());
+                        }
+                    }
+                }
+                if removed_all {
+                    let config_path = dir.join(&configuration_manager.configuration_file_name);
+                    if config_path.exists() {
+                        fs::remove_file(&config_path).await?;
+                    }
+                    // Only goes if nothing else was left behind in it
+                    let _ = fs::remove_dir(&dir).await;
+                }
+            }
+        },
+        Command::Exec {
+            run_command,
+            output,
+            fail_fast,
+            timeout,
+            parallel,
+            if_exists,
+            if_file_matches,
+            if_dirty,
+            if_ahead,
+            argv,
+        } => {
+            let command = match run_command {
+                Some(line) => ExecCommand::Shell(line),
+                None => ExecCommand::Argv(argv),
+            };
+            let conditions = RunConditions {
+                if_exists,
+                if_file_matches,
+                if_dirty,
+                if_ahead,
+            };
+            let mut repos = vec![];
+            let mut skipped = vec![];
+            for &repo in &selected {
+                match conditions.matches(repo) {
+                    Ok(true) => repos.push(repo.clone()),
+                    Ok(false) => skipped.push(repo.name.as_str()),
+                    Err(e) => println!("{}: skipped, {}", repo.name, e.to_string().red()),
+                }
+            }
+            if !skipped.is_empty() {
+                println!("Skipping {}, conditions not met", skipped.join(", "));
+            }
+            if repos.is_empty() {
+                println!("No repositories to run in");
+                return Ok(());
+            }
+
+            let log_dir = exec::log_directory(&configuration_manager.configuration_path);
+            fs::create_dir_all(&log_dir).await?;
+            let options = ExecOptions {
+                // Sections make no sense once output from several repositories interleaves
+                style: if parallel > 1 {
+                    OutputStyle::Prefixed
+                } else {
+                    output
+                },
+                shell: configuration_manager.shell.clone(),
+                timeout: timeout.map(Duration::from_secs),
+                log_dir: Some(log_dir.clone()),
+                prefix_width: repos.iter().map(|r| r.name.len()).max().unwrap_or(0),
+            };
+            let results = exec::run_all(repos, command, options, parallel, fail_fast).await;
+            exec::print_summary(&results);
+            println!("Logs saved to {}", log_dir.display());
+            if results.iter().any(|r| !r.success()) {
+                std::process::exit(1);
+            }
+        }
+        Command::Run { name } => {
+            let Some(name) = name else {
+                if configuration_manager.commands.is_empty() {
+                    println!(
+                        "No commands defined in {}",
+                        configuration_manager.configuration_full_path
+                    );
+                }
+                for (name, command) in &configuration_manager.commands {
+                    println!("{} {}", name.clone().bold(), command.description);
+                }
+                return Ok(());
+            };
+            let command = configuration_manager
+                .commands
+                .get(&name)
+                .ok_or_else(|| anyhow::anyhow!("No command named '{name}' in the configuration"))?;
+            if !workflow::run_user_command(&name, command, &args.repos).await? {
+                std::process::exit(1);
+            }
+        }
+        Command::External(external) => {
+            let (subcommand, plugin_args) = external
+                .split_first()
+                .ok_or_else(|| anyhow::anyhow!("Missing subcommand"))?;
+            let executable = std::env::var_os("PATH")
+                .and_then(|path| plugin::find_in_path(&plugin::plugin_name(subcommand), &path))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Unknown command '{subcommand}', no {} found on PATH",
+                        plugin::plugin_name(subcommand)
+                    )
+                })?;
+            let status = plugin::run_plugin(
+                &executable,
+                plugin_args,
+                std::path::Path::new(&configuration_manager.configuration_full_path),
+                &selected,
+            )
+            .await?;
+            if !status.success() {
+                std::process::exit(status.code().unwrap_or(1));
             }
         }
-        Command::Remove { name } => {
+        Command::Remove { .. } => {
             println!("Removing repository...");
         }
     }
     Ok(())
 }
 
+fn branch_display_name(branch: &BranchInfo) -> String {
+    match &branch.remote {
+        Some(remote) => format!("{}/{}", remote, branch.name),
+        None => branch.name.clone(),
+    }
+}
+
+fn print_branch_list(overview: &[RepositoryBranches]) {
+    for entry in overview {
+        println!("{}", entry.repository.clone().bold());
+        if entry.branches.is_empty() {
+            println!("  no matching branches");
+        }
+        for branch in &entry.branches {
+            println!(
+                "  {} commit:{:.8}, last commit: {} by {}, ahead: {}, behind: {}",
+                branch_display_name(branch),
+                branch.commit_sha,
+                branch.last_commit_date.format("%Y-%m-%d %H:%M"),
+                branch.author,
+                branch.ahead,
+                branch.behind
+            );
+        }
+    }
+}
+
+/// One row per branch name and one column per repository, cells show ahead/behind counts
+fn print_branch_matrix(overview: &[RepositoryBranches]) {
+    let rows: BTreeSet<String> = overview
+        .iter()
+        .flat_map(|entry| entry.branches.iter().map(branch_display_name))
+        .collect();
+    if rows.is_empty() {
+        println!("No matching branches");
+        return;
+    }
+    let name_width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+
+    let mut header = format!("{:name_width$}", "");
+    for entry in overview {
+        header.push_str(&format!("  {:>8}", entry.repository));
+    }
+    println!("{header}");
+
+    for row in rows {
+        let mut line = format!("{row:name_width$}");
+        for entry in overview {
+            let width = entry.repository.len().max(8);
+            let cell = entry
+                .branches
+                .iter()
+                .find(|b| branch_display_name(b) == row)
+                .map(|b| format!("+{}/-{}", b.ahead, b.behind))
+                .unwrap_or_else(|| "-".to_string());
+            line.push_str(&format!("  {cell:>width$}"));
+        }
+        println!("{line}");
+    }
+}
+
+/// Fetch each repository on its own blocking thread, results in the order given
+async fn fetch_all(
+    selected: &[&Repository],
+    repo_manager: &Manager,
+    settings: &FetchSettings,
+) -> Vec<(String, Result<Vec<FetchedRef>, anyhow::Error>)> {
+    let mut tasks = JoinSet::new();
+    for (position, &repo) in selected.iter().enumerate() {
+        let (repo, repo_manager, settings) = (repo.clone(), repo_manager.clone(), settings.clone());
+        tasks.spawn_blocking(move || {
+            let result = repo_manager.fetch(&repo, &settings);
+            (position, repo.name, result)
+        });
+    }
+    let mut results = tasks.join_all().await;
+    results.sort_by_key(|(position, _, _)| *position);
+    results
+        .into_iter()
+        .map(|(_, name, result)| (name, result))
+        .collect()
+}
+
+/// Rough age such as "5 minutes ago"
+fn time_ago(age: chrono::TimeDelta) -> String {
+    let (amount, unit) = if age.num_days() > 0 {
+        (age.num_days(), "day")
+    } else if age.num_hours() > 0 {
+        (age.num_hours(), "hour")
+    } else if age.num_minutes() > 0 {
+        (age.num_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    format!("{amount} {unit}{} ago", if amount == 1 { "" } else { "s" })
+}
+
+/// Counts of the refs a fetch changed, then one line per ref
+fn print_fetched(name: &str, fetched: &[FetchedRef]) {
+    let count = |change| fetched.iter().filter(|f| f.change == change).count();
+    println!(
+        "{}: {} new, {} updated, {} deleted",
+        name.to_string().bold(),
+        count(RefChange::New),
+        count(RefChange::Updated),
+        count(RefChange::Deleted)
+    );
+    let short = |sha: &Option<String>| sha.as_deref().map(|s| s[..8].to_string());
+    for fetched_ref in fetched {
+        let line = match fetched_ref.change {
+            RefChange::New => format!(
+                "  + {} {}",
+                fetched_ref.refname,
+                short(&fetched_ref.new_sha).unwrap_or_default()
+            )
+            .green(),
+            RefChange::Updated => format!(
+                "  ~ {} {}..{}",
+                fetched_ref.refname,
+                short(&fetched_ref.old_sha).unwrap_or_default(),
+                short(&fetched_ref.new_sha).unwrap_or_default()
+            )
+            .yellow(),
+            RefChange::Deleted => format!("  - {}", fetched_ref.refname).red(),
+        };
+        println!("{line}");
+    }
+}
+
+/// Every ref a push sent and whether the remote took it, with the server's
+/// messages when something was turned down
+fn print_push_report(name: &str, report: &PushReport) {
+    for update in &report.updates {
+        match &update.rejection {
+            None => println!("{name}: {} {}", update.refname, "accepted".green()),
+            Some(reason) => println!(
+                "{name}: {} {}",
+                update.refname,
+                format!("rejected ({reason})").red()
+            ),
+        }
+    }
+    if !report.accepted() {
+        for message in &report.messages {
+            println!("{name}: remote: {message}");
+        }
+    }
+}
+
+/// One line per repository pushed, in the style of the exec summary
+fn print_push_summary(outcomes: &[(&str, Result<PushReport, anyhow::Error>)]) {
+    let width = outcomes
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    println!();
+    println!("{}", "Summary".bold());
+    for (name, result) in outcomes {
+        let outcome = match result {
+            Ok(report) if report.accepted() => {
+                format!("pushed {} to {}", report.branch, report.remote).green()
+            }
+            Ok(report) if report.updates.is_empty() => {
+                format!("{} reported no ref updates", report.remote).red()
+            }
+            Ok(report) => {
+                let rejected = report.updates.iter().filter(|u| u.rejection.is_some());
+                format!("rejected {} ref(s)", rejected.count()).red()
+            }
+            Err(e) => format!("failed ({e})").red(),
+        };
+        println!("  {name:width$}  {outcome}");
+    }
+}
+
+/// The commits a repository would push, under a header with their count
+fn print_outgoing(repo_manager: &Manager, repo: &Repository, remote: &str) {
+    match repo_manager.outgoing(repo, remote) {
+        Ok(commits) if commits.is_empty() => println!("{}: nothing to push", repo.name),
+        Ok(commits) => {
+            println!(
+                "{}: {} outgoing commit(s)",
+                repo.name.clone().bold(),
+                commits.len()
+            );
+            print_commits(&commits);
+        }
+        Err(e) => println!("{}: {}", repo.name, e.to_string().red()),
+    }
+}
+
+/// One line per commit: short sha, subject and author
+fn print_commits(commits: &[CommitSummary]) {
+    for commit in commits {
+        println!(
+            "  {} {} ({})",
+            commit.sha.clone().yellow(),
+            commit.summary,
+            commit.author
+        );
+    }
+}
+
+/// Ask a yes/no question on stdin, anything other than an explicit yes is treated as no
+fn confirm(prompt: &str) -> Result<bool, anyhow::Error> {
+    println!("{prompt}");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let re = Regex::new("(?i)^y(es)?$").unwrap();
+    Ok(re.is_match(input.trim()))
+}
+
 async fn add_repo(
-    organization: String,
-    name: String,
+    repository: Repository,
     mut configuration_manager: Configuration,
     repo_manager: Manager,
     add_to_config: bool,
 ) -> Result<(), anyhow::Error> {
-    let mut repository = Repository::default();
     if add_to_config {
-        repository.name = name.clone();
-        repository.organization = organization.clone();
         configuration_manager.add_repository(repository.clone());
         configuration_manager
             .save()
@@ -263,14 +1437,85 @@ async fn add_repo(
             .expect("Could not save configuration");
     }
     // Pull the repository and update the index
-    let checkout_info = repo_manager.clone_repo(organization, name.clone())?;
-    let saved_repo = configuration_manager.get_repository_mut(name);
+    let checkout_info = repo_manager.clone_repo(
+        repository.organization,
+        repository.name.clone(),
+        &repository.clone_options,
+    )?;
+    let saved_repo = configuration_manager.get_repository_mut(repository.name);
     saved_repo.checkout_info = checkout_info.clone();
     saved_repo.cloned_locally = true;
+    let saved_repo = saved_repo.clone();
     configuration_manager
         .save()
         .await
         .expect("Could not save configuration");
+    ensure_remotes(&repo_manager, &saved_repo);
+    update_submodules(&repo_manager, &saved_repo);
+    apply_sparse_paths(&repo_manager, &saved_repo);
+    run_post_hook(&configuration_manager, &saved_repo, Hook::PostClone).await;
 
     Ok(())
 }
+
+/// Summary of submodule states for list, empty when there are no submodules
+fn describe_submodules(submodules: &[SubmoduleStatus]) -> String {
+    if submodules.is_empty() {
+        return String::new();
+    }
+    let count = |state: SubmoduleState| submodules.iter().filter(|s| s.state == state).count();
+    let problems: Vec<String> = [
+        (SubmoduleState::Uninitialized, "not initialised"),
+        (SubmoduleState::OutOfDate, "out of date"),
+        (SubmoduleState::Dirty, "dirty"),
+    ]
+    .into_iter()
+    .filter_map(|(state, label)| match count(state) {
+        0 => None,
+        n => Some(format!("{n} {label}")),
+    })
+    .collect();
+    let summary = if problems.is_empty() {
+        format!("{} up to date", submodules.len()).green()
+    } else {
+        problems.join(", ").yellow()
+    };
+    format!(", submodules: {summary}")
+}
+
+/// Add the configured remotes a clone is missing, reporting failures
+fn ensure_remotes(repo_manager: &Manager, repo: &Repository) {
+    match repo_manager.ensure_remotes(repo) {
+        Ok(touched) => {
+            for name in touched {
+                println!("{}: remote {} set up", repo.name, name);
+            }
+        }
+        Err(e) => println!("{}: {}", repo.name, e.to_string().yellow()),
+    }
+}
+
+/// Update submodules of repositories configured to recurse into them, reporting failures
+fn update_submodules(repo_manager: &Manager, repo: &Repository) {
+    if !repo.recurse_submodules {
+        return;
+    }
+    if let Err(e) = repo_manager.update_submodules(repo) {
+        println!("{}: {}", repo.name, e.to_string().yellow());
+    }
+}
+
+/// Bring the working tree in line with the configured sparse paths, reporting failures
+fn apply_sparse_paths(repo_manager: &Manager, repo: &Repository) {
+    if let Err(e) = repo_manager.apply_sparse_paths(repo) {
+        println!("{}: {}", repo.name, e.to_string().yellow());
+    }
+}
+
+/// Run the hooks that follow an operation, failures are reported but do not undo it
+async fn run_post_hook(configuration_manager: &Configuration, repo: &Repository, hook: Hook) {
+    let commands = configuration_manager.hook_commands(repo, hook);
+    if let Err(e) = exec::run_hook(repo, hook, &commands, &configuration_manager.shell).await {
+        println!("{}: {}", repo.name, e.to_string().yellow());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::Repository;
+use anyhow::anyhow;
+use std::env;
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Executable name external subcommands are looked up by, `palette-<name>`
+pub fn plugin_name(subcommand: &str) -> String {
+    format!("palette-{subcommand}")
+}
+
+/// Find an executable called `name` in the directories of a PATH style list
+pub fn find_in_path(name: &str, path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+}
+
+/// Run a plugin with the selected repositories as JSON on stdin, exporting
+/// `PALETTE_CONFIG` and `PALETTE_REPOSITORIES` so it can find the workspace
+pub async fn run_plugin(
+    executable: &Path,
+    args: &[String],
+    configuration_path: &Path,
+    repositories: &[&Repository],
+) -> Result<ExitStatus, anyhow::Error> {
+    let configuration_path = std::fs::canonicalize(configuration_path)
+        .unwrap_or_else(|_| configuration_path.to_path_buf());
+    let names: Vec<&str> = repositories.iter().map(|r| r.name.as_str()).collect();
+
+    let mut child = Command::new(executable)
+        .args(args)
+        .env("PALETTE_CONFIG", &configuration_path)
+        .env("PALETTE_REPOSITORIES", names.join(","))
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Unable to run {}: {e}", executable.display()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let payload = serde_json::to_vec(repositories)?;
+        // Plugins that do not read stdin close it early, which is fine
+        let _ = stdin.write_all(&payload).await;
+    }
+    Ok(child.wait().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn write_script(path: &Path, content: &str, mode: u32) {
+        fs::write(path, content).expect("Failed to write script");
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_find_in_path_requires_executable() {
+        let first = tempdir().expect("Failed to create temp dir");
+        let second = tempdir().expect("Failed to create temp dir");
+        write_script(&first.path().join("palette-hello"), "", 0o644);
+        write_script(&second.path().join("palette-hello"), "", 0o755);
+
+        let path = env::join_paths([first.path(), second.path()]).unwrap();
+        assert_eq!(
+            find_in_path(&plugin_name("hello"), &path),
+            Some(second.path().join("palette-hello"))
+        );
+        assert_eq!(find_in_path(&plugin_name("missing"), &path), None);
+    }
+
+    #[tokio::test]
+    async fn test_run_plugin_receives_workspace() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let output = temp_dir.path().join("output");
+        let plugin = temp_dir.path().join("palette-report");
+        write_script(
+            &plugin,
+            &format!(
+                "#!/bin/sh\necho \"$1 $PALETTE_REPOSITORIES\" > {0}\ncat >> {0}\nexit 4\n",
+                output.display()
+            ),
+            0o755,
+        );
+        let config = temp_dir.path().join("config.palette");
+        let repos = [
+            Repository {
+                name: "one".to_string(),
+                ..Default::default()
+            },
+            Repository {
+                name: "two".to_string(),
+                ..Default::default()
+            },
+        ];
+        let selected: Vec<&Repository> = repos.iter().collect();
+
+        let status = run_plugin(&plugin, &["arg".to_string()], &config, &selected)
+            .await
+            .unwrap();
+        assert_eq!(status.code(), Some(4));
+
+        let written = fs::read_to_string(output).unwrap();
+        let (first_line, json) = written.split_once('\n').unwrap();
+        assert_eq!(first_line, "arg one,two");
+        let parsed: Vec<Repository> = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].name, "two");
+    }
+}
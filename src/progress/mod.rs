@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the terminal display is redrawn
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// How often a plain progress line is logged for the same repository
+const LOG_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct State {
+    /// Repositories with an operation in flight and their latest progress, in start order
+    lines: Vec<(String, String)>,
+    /// Lines currently drawn below the cursor's starting point
+    drawn: usize,
+    last_redraw: Option<Instant>,
+    last_logged: HashMap<String, Instant>,
+}
+
+/// Progress of network operations in several repositories at once. On a
+/// terminal each repository in flight gets a line that is updated in place,
+/// otherwise progress is logged as ordinary lines every so often.
+#[derive(Clone)]
+pub struct Progress {
+    interactive: bool,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress::new(io::stdout().is_terminal())
+    }
+}
+
+impl Progress {
+    pub fn new(interactive: bool) -> Self {
+        Progress {
+            interactive,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Record the latest progress of the operation running in a repository
+    pub fn update(&self, repo: &str, text: String) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if self.interactive {
+            match state.lines.iter_mut().find(|(name, _)| name == repo) {
+                Some((_, line)) => *line = text,
+                None => state.lines.push((repo.to_string(), text)),
+            }
+            if state
+                .last_redraw
+                .is_none_or(|at| now.duration_since(at) >= REDRAW_INTERVAL)
+            {
+                state.last_redraw = Some(now);
+                redraw(&mut state);
+            }
+        } else if state
+            .last_logged
+            .get(repo)
+            .is_none_or(|at| now.duration_since(*at) >= LOG_INTERVAL)
+        {
+            state.last_logged.insert(repo.to_string(), now);
+            println!("{repo}: {text}");
+        }
+    }
+
+    /// Drop a repository's line once its operation is over
+    pub fn finish(&self, repo: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.last_logged.remove(repo);
+        let before = state.lines.len();
+        state.lines.retain(|(name, _)| name != repo);
+        if self.interactive && state.lines.len() != before {
+            redraw(&mut state);
+        }
+    }
+}
+
+/// Replace the lines drawn last time with the current ones
+fn redraw(state: &mut State) {
+    let mut stdout = io::stdout().lock();
+    if state.drawn > 0 {
+        // Move back up to the first line and clear everything below it
+        let _ = write!(stdout, "\x1b[{}A\x1b[J", state.drawn);
+    }
+    for (name, text) in &state.lines {
+        let _ = writeln!(stdout, "{name}: {text}");
+    }
+    let _ = stdout.flush();
+    state.drawn = state.lines.len();
+}
+
+/// A transfer stage such as "receiving objects 45% (450/1000), 1.2 MiB"
+pub fn describe(stage: &str, done: usize, total: usize, bytes: Option<usize>) -> String {
+    let percent = (done * 100).checked_div(total).unwrap_or(100);
+    let mut text = format!("{stage} {percent}% ({done}/{total})");
+    if let Some(bytes) = bytes {
+        text.push_str(&format!(", {}", human_bytes(bytes)));
+    }
+    text
+}
+
+/// Where a fetch has got to: nothing known before the remote says how many objects
+/// are coming, then receiving them, then resolving deltas
+pub fn receiving(
+    received_objects: usize,
+    total_objects: usize,
+    received_bytes: usize,
+    indexed_deltas: usize,
+    total_deltas: usize,
+) -> String {
+    if total_objects == 0 {
+        "connecting".to_string()
+    } else if received_objects < total_objects {
+        describe(
+            "receiving objects",
+            received_objects,
+            total_objects,
+            Some(received_bytes),
+        )
+    } else {
+        describe("resolving deltas", indexed_deltas, total_deltas, None)
+    }
+}
+
+/// Lines the remote sent on its progress channel, such as "Counting objects: 45% (9/20)"
+pub fn remote_lines(data: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(data)
+        .split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Byte count in the largest binary unit that keeps it above one
+pub fn human_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_bytes() {
+        assert_eq!(human_bytes(0), "0 B");
+        assert_eq!(human_bytes(1023), "1023 B");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("receiving objects", 450, 1000, Some(2048)),
+            "receiving objects 45% (450/1000), 2.0 KiB"
+        );
+        assert_eq!(
+            describe("resolving deltas", 0, 0, None),
+            "resolving deltas 100% (0/0)"
+        );
+    }
+
+    #[test]
+    fn test_receiving() {
+        assert_eq!(receiving(0, 0, 0, 0, 0), "connecting");
+        assert_eq!(
+            receiving(5, 10, 1024, 0, 0),
+            "receiving objects 50% (5/10), 1.0 KiB"
+        );
+        assert_eq!(receiving(10, 10, 1024, 2, 4), "resolving deltas 50% (2/4)");
+    }
+
+    #[test]
+    fn test_remote_lines() {
+        assert_eq!(
+            remote_lines(b"Counting objects:  50% (1/2)\rCounting objects: 100% (2/2), done.\n"),
+            vec![
+                "Counting objects:  50% (1/2)".to_string(),
+                "Counting objects: 100% (2/2), done.".to_string()
+            ]
+        );
+        assert!(remote_lines(b"\r\n").is_empty());
+    }
+
+    #[test]
+    fn test_finish_drops_line() {
+        let progress = Progress::new(false);
+        progress.update("alpha", "receiving objects".to_string());
+        assert!(
+            progress
+                .state
+                .lock()
+                .unwrap()
+                .last_logged
+                .contains_key("alpha")
+        );
+        progress.finish("alpha");
+        assert!(progress.state.lock().unwrap().last_logged.is_empty());
+    }
+}
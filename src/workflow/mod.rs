@@ -0,0 +1,180 @@
+use crate::config::{CommandStep, FailurePolicy, UserCommand};
+use anyhow::anyhow;
+use colorize::AnsiColor;
+use std::env;
+use tokio::process::Command;
+
+/// Counts how deeply user defined commands are running one another
+const DEPTH_VARIABLE: &str = "PALETTE_RUN_DEPTH";
+/// Deeper than this a command is almost certainly running itself
+const MAX_DEPTH: u32 = 8;
+
+/// Nesting depth of the command about to run, given the depth its parent exported
+fn nesting_depth(parent: Option<String>) -> Result<u32, anyhow::Error> {
+    let depth = parent.and_then(|d| d.parse::<u32>().ok()).unwrap_or(0) + 1;
+    if depth > MAX_DEPTH {
+        return Err(anyhow!(
+            "commands are nested more than {MAX_DEPTH} deep, does a step run the command it belongs to?"
+        ));
+    }
+    Ok(depth)
+}
+
+/// Repositories a step applies to, narrowing the selection palette was run with
+fn step_repositories(step: &CommandStep, selected: &[String]) -> Vec<String> {
+    match (step.repos.is_empty(), selected.is_empty()) {
+        (true, _) => selected.to_vec(),
+        (false, true) => step.repos.clone(),
+        (false, false) => step
+            .repos
+            .iter()
+            .filter(|name| selected.contains(name))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Arguments palette is invoked with to carry out a step
+pub fn step_arguments(
+    step: &CommandStep,
+    selected: &[String],
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut args = vec![];
+    let repos = step_repositories(step, selected);
+    if !repos.is_empty() {
+        args.push("--repos".to_string());
+        args.push(repos.join(","));
+    }
+    match (&step.shell, step.palette.is_empty()) {
+        (Some(line), true) => args.extend([
+            "exec".to_string(),
+            "--run-command".to_string(),
+            line.clone(),
+        ]),
+        (None, false) => args.extend(step.palette.iter().cloned()),
+        _ => return Err(anyhow!("a step needs exactly one of `palette` or `shell`")),
+    }
+    Ok(args)
+}
+
+/// Run each step of a user defined command in order, returning whether every step succeeded
+pub async fn run_user_command(
+    name: &str,
+    command: &UserCommand,
+    selected: &[String],
+) -> Result<bool, anyhow::Error> {
+    let depth = nesting_depth(env::var(DEPTH_VARIABLE).ok())?;
+    let palette = env::current_exe()?;
+    let mut succeeded = true;
+
+    for (position, step) in command.steps.iter().enumerate() {
+        let args = step_arguments(step, selected)
+            .map_err(|e| anyhow!("{name} step {}: {e}", position + 1))?;
+        // Selectors on both sides can leave nothing for this step to do
+        if !step.repos.is_empty() && step_repositories(step, selected).is_empty() {
+            println!(
+                "{}",
+                format!(
+                    "==> [{}/{}] skipped, no selected repositories",
+                    position + 1,
+                    command.steps.len()
+                )
+                .bold()
+            );
+            continue;
+        }
+        println!(
+            "{}",
+            format!(
+                "==> [{}/{}] palette {}",
+                position + 1,
+                command.steps.len(),
+                args.join(" ")
+            )
+            .bold()
+        );
+
+        // Steps inherit the depth, including palette run in a shell step
+        let status = Command::new(&palette)
+            .args(&args)
+            .env(DEPTH_VARIABLE, depth.to_string())
+            .status()
+            .await?;
+        if status.success() {
+            continue;
+        }
+        succeeded = false;
+        match step.on_failure {
+            FailurePolicy::Stop => {
+                println!(
+                    "{}",
+                    format!("{name} stopped, step {} failed", position + 1).red()
+                );
+                break;
+            }
+            FailurePolicy::Continue => println!(
+                "{}",
+                format!("Step {} failed, continuing", position + 1).yellow()
+            ),
+        }
+    }
+    Ok(succeeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_palette_step_arguments() {
+        let step = CommandStep {
+            palette: names(&["pull"]),
+            ..Default::default()
+        };
+        assert_eq!(step_arguments(&step, &[]).unwrap(), names(&["pull"]));
+        assert_eq!(
+            step_arguments(&step, &names(&["a", "b"])).unwrap(),
+            names(&["--repos", "a,b", "pull"])
+        );
+    }
+
+    #[test]
+    fn test_shell_step_arguments_narrow_selection() {
+        let step = CommandStep {
+            shell: Some("cargo build".to_string()),
+            repos: names(&["a", "c"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            step_arguments(&step, &[]).unwrap(),
+            names(&["--repos", "a,c", "exec", "--run-command", "cargo build"])
+        );
+        assert_eq!(
+            step_arguments(&step, &names(&["a", "b"])).unwrap(),
+            names(&["--repos", "a", "exec", "--run-command", "cargo build"])
+        );
+    }
+
+    #[test]
+    fn test_step_needs_exactly_one_action() {
+        assert!(step_arguments(&CommandStep::default(), &[]).is_err());
+
+        let both = CommandStep {
+            palette: names(&["list"]),
+            shell: Some("ls".to_string()),
+            ..Default::default()
+        };
+        assert!(step_arguments(&both, &[]).is_err());
+    }
+
+    #[test]
+    fn test_nesting_depth_is_limited() {
+        assert_eq!(nesting_depth(None).unwrap(), 1);
+        assert_eq!(nesting_depth(Some("3".to_string())).unwrap(), 4);
+        assert!(nesting_depth(Some(MAX_DEPTH.to_string())).is_err());
+    }
+}
@@ -12,6 +12,7 @@ async fn test_configuration_integration() {
         configuration_file_name: "config.palette".to_string(),
         configuration_full_path: config_path.to_str().unwrap().to_string(),
         repository: vec![],
+        ..Default::default()
     };
 
     // Test adding repositories
@@ -23,6 +24,7 @@ async fn test_configuration_integration() {
             branch_name: "main".to_string(),
             commit_sha: "abc123".to_string(),
         },
+        ..Default::default()
     };
 
     let repo2 = Repository {
@@ -33,6 +35,7 @@ async fn test_configuration_integration() {
             branch_name: "develop".to_string(),
             commit_sha: "def456".to_string(),
         },
+        ..Default::default()
     };
 
     config.add_repository(repo1);